@@ -37,10 +37,9 @@ def compress_with_rust(input_data):
         
         # Run the Rust compression algorithm
         result = subprocess.run([
-            'cargo', 'run', '--', 
-            '--input', temp_input_path,
+            'cargo', 'run', '--', 'compress',
+            temp_input_path,
             '--output', temp_output_path,
-            '--mode', 'compress',
             '--algorithm', 'huffman'
         ], 
         cwd='/Users/hunterbroughton/compression_algorithm',
@@ -63,8 +62,8 @@ def compress_with_rust(input_data):
                 # If no output file, use the original data (compression failed)
                 compressed_data = input_data.encode('utf-8')
             
-            # Parse the output to get compression statistics
-            output_lines = result.stdout.strip().split('\n')
+            # Parse the output to get compression statistics (the CLI reports them on stderr)
+            output_lines = result.stderr.strip().split('\n')
             stats = {
                 'original_size': len(input_data.encode('utf-8')),
                 'compressed_size': len(compressed_data),
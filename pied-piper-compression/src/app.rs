@@ -18,7 +18,7 @@ use compression_algorithm::compression::{huffman::HuffmanCoding, CompressionAlgo
 pub async fn compress_text(text: String) -> Result<String, ServerFnError> {
     use compression_algorithm::compression::{huffman::HuffmanCoding, CompressionAlgorithm};
     
-    let huffman = HuffmanCoding;
+    let huffman = HuffmanCoding::default();
     let input_bytes = text.into_bytes();
     let input_size = input_bytes.len();
     
@@ -0,0 +1,404 @@
+// Multi-file archive format written by `pack` and read by `unpack`/`list`.
+//
+// Layout:
+//   magic      4 bytes  "PPAR"
+//   version    1 byte
+//   entry data, back to back, each compressed with its own algorithm
+//   central directory, one record per entry:
+//     path_len u16, path (UTF-8, '/' separated)
+//     size u64, compressed_size u64, offset u64 (from the start of the archive)
+//     mode u32, mtime i64 (seconds since the Unix epoch)
+//     spec_len u8, spec (algorithm spec, or "store" for uncompressed data)
+//     crc32 u32 of the uncompressed data
+//   footer:
+//     directory offset u64, entry count u32, "PPAE"
+//
+// The directory sits at the end, as in zip, so entries can be written as they
+// are compressed and listed without reading their data.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::checksum::crc32;
+use crate::compression::{algorithm_from_spec_with_options, CodecOptions};
+
+pub const MAGIC: &[u8; 4] = b"PPAR";
+pub const VERSION: u8 = 1;
+pub const FOOTER_MAGIC: &[u8; 4] = b"PPAE";
+
+/// Spec recorded for entries kept uncompressed because compression did not help
+pub const STORED: &str = "store";
+
+const FOOTER_SIZE: usize = 16;
+
+// Mode bits restored by default. Setuid, setgid and sticky bits from an
+// untrusted archive could leave an extracted program running as its owner.
+const PERMISSION_BITS: u32 = 0o777;
+
+/// Permissions and modification time recorded for an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Unix permission bits (0o644 style)
+    pub mode: u32,
+    /// Seconds since the Unix epoch
+    pub mtime: i64,
+}
+
+impl FileMetadata {
+    /// Read the metadata of the file at `path`
+    pub fn from_path(path: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime = match metadata.modified()?.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        };
+        Ok(FileMetadata { mode: file_mode(&metadata), mtime })
+    }
+
+    /// Apply the permission bits and modification time to the file at `path`,
+    /// leaving out any setuid, setgid and sticky bits
+    pub fn apply(&self, path: &Path) -> std::io::Result<()> {
+        self.apply_mode(path, self.mode & PERMISSION_BITS)
+    }
+
+    /// Like `apply`, but also restore the setuid, setgid and sticky bits
+    pub fn apply_with_special_bits(&self, path: &Path) -> std::io::Result<()> {
+        self.apply_mode(path, self.mode)
+    }
+
+    fn apply_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        let mtime = if self.mtime >= 0 {
+            UNIX_EPOCH + Duration::from_secs(self.mtime as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(self.mtime.unsigned_abs())
+        };
+        // Permissions go last: a read-only mode would stop the mtime update
+        fs::File::options().write(true).open(path)?.set_modified(mtime)?;
+        set_file_mode(path, mode)
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() { 0o444 } else { 0o644 }
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+/// One file in the archive, as recorded in the central directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub offset: u64,
+    pub metadata: FileMetadata,
+    pub algorithm: String,
+    pub crc32: u32,
+}
+
+/// Builds an archive in memory, compressing entries as they are added
+pub struct ArchiveWriter {
+    output: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+impl Default for ArchiveWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveWriter {
+    pub fn new() -> Self {
+        let mut output = Vec::new();
+        output.extend_from_slice(MAGIC);
+        output.push(VERSION);
+        ArchiveWriter { output, entries: Vec::new() }
+    }
+
+    /// Compress `data` with `algorithm` and add it as `path`. The entry is stored
+    /// uncompressed instead if compressing would make it larger.
+    pub fn add(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        metadata: FileMetadata,
+        algorithm: &str,
+        options: &CodecOptions,
+    ) -> Result<&Entry, Box<dyn std::error::Error>> {
+        validate_path(path)?;
+        if path.len() > u16::MAX as usize {
+            return Err(format!("Path too long for the archive directory: {}", path).into());
+        }
+        if self.entries.iter().any(|entry| entry.path == path) {
+            return Err(format!("Duplicate archive entry: {}", path).into());
+        }
+
+        let compressed = algorithm_from_spec_with_options(algorithm, options)?.compress(data)?;
+        let (algorithm, payload) = if compressed.len() < data.len() {
+            (algorithm, &compressed[..])
+        } else {
+            (STORED, data)
+        };
+        if algorithm.len() > u8::MAX as usize {
+            return Err(format!("Algorithm spec too long for the archive directory: {}", algorithm).into());
+        }
+
+        self.entries.push(Entry {
+            path: path.to_string(),
+            size: data.len() as u64,
+            compressed_size: payload.len() as u64,
+            offset: self.output.len() as u64,
+            metadata,
+            algorithm: algorithm.to_string(),
+            crc32: crc32(data),
+        });
+        self.output.extend_from_slice(payload);
+        Ok(self.entries.last().unwrap())
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Append the central directory and return the finished archive
+    pub fn finish(mut self) -> Vec<u8> {
+        let directory_offset = self.output.len() as u64;
+        for entry in &self.entries {
+            let output = &mut self.output;
+            output.extend_from_slice(&(entry.path.len() as u16).to_le_bytes());
+            output.extend_from_slice(entry.path.as_bytes());
+            output.extend_from_slice(&entry.size.to_le_bytes());
+            output.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            output.extend_from_slice(&entry.offset.to_le_bytes());
+            output.extend_from_slice(&entry.metadata.mode.to_le_bytes());
+            output.extend_from_slice(&entry.metadata.mtime.to_le_bytes());
+            output.push(entry.algorithm.len() as u8);
+            output.extend_from_slice(entry.algorithm.as_bytes());
+            output.extend_from_slice(&entry.crc32.to_le_bytes());
+        }
+
+        self.output.extend_from_slice(&directory_offset.to_le_bytes());
+        self.output.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        self.output.extend_from_slice(FOOTER_MAGIC);
+        self.output
+    }
+}
+
+/// Reject paths that could escape the directory an archive is unpacked into
+pub fn validate_path(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let unsafe_component = path
+        .split('/')
+        .any(|component| component.is_empty() || component == "." || component == ".." || component.contains('\\'));
+    if unsafe_component || path.contains(':') {
+        return Err(format!("Unsafe archive path: {:?}", path).into());
+    }
+    Ok(())
+}
+
+/// An archive read from memory
+pub struct Archive<'a> {
+    data: &'a [u8],
+    entries: Vec<Entry>,
+}
+
+/// Reads values from the central directory
+struct DirectoryReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> DirectoryReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or("Truncated archive directory")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "Archive directory has a non UTF-8 string")?)
+    }
+}
+
+/// Check whether `data` starts with the archive magic
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+impl<'a> Archive<'a> {
+    /// Read the central directory of the archive in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        if !is_archive(data) {
+            return Err("Not a Pied Piper archive".into());
+        }
+        let version = *data.get(MAGIC.len()).ok_or("Truncated archive header")?;
+        if version != VERSION {
+            return Err(format!("Unsupported archive version {}", version).into());
+        }
+        if data.len() < MAGIC.len() + 1 + FOOTER_SIZE || !data.ends_with(FOOTER_MAGIC) {
+            return Err("Truncated archive (missing directory footer)".into());
+        }
+
+        let footer_start = data.len() - FOOTER_SIZE;
+        let mut footer = DirectoryReader { data, pos: footer_start };
+        let directory_offset = footer.u64()? as usize;
+        let count = footer.u32()? as usize;
+        if directory_offset < MAGIC.len() + 1 || directory_offset > footer_start {
+            return Err("Archive directory offset is out of range".into());
+        }
+
+        let mut reader = DirectoryReader { data: &data[..footer_start], pos: directory_offset };
+        let mut entries = Vec::with_capacity(count.min(data.len()));
+        for _ in 0..count {
+            let path_len = u16::from_le_bytes(reader.take(2)?.try_into()?) as usize;
+            let path = reader.string(path_len)?;
+            let size = reader.u64()?;
+            let compressed_size = reader.u64()?;
+            let offset = reader.u64()?;
+            let mode = reader.u32()?;
+            let mtime = reader.u64()? as i64;
+            let spec_len = reader.take(1)?[0] as usize;
+            let algorithm = reader.string(spec_len)?;
+            let crc32 = reader.u32()?;
+            validate_path(&path)?;
+
+            let end = offset.checked_add(compressed_size);
+            if offset < (MAGIC.len() + 1) as u64 || end.is_none_or(|end| end > directory_offset as u64) {
+                return Err(format!("Archive entry '{}' points outside the entry data", path).into());
+            }
+            entries.push(Entry { path, size, compressed_size, offset, metadata: FileMetadata { mode, mtime }, algorithm, crc32 });
+        }
+        if reader.pos != footer_start {
+            return Err("Archive directory has trailing bytes".into());
+        }
+
+        Ok(Archive { data, entries })
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Decompress `entry` and check it against its recorded size and CRC-32
+    pub fn extract(&self, entry: &Entry, options: &CodecOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let start = entry.offset as usize;
+        let payload = &self.data[start..start + entry.compressed_size as usize];
+
+        let data = if entry.algorithm == STORED {
+            payload.to_vec()
+        } else {
+            algorithm_from_spec_with_options(&entry.algorithm, options)?.decompress(payload)?
+        };
+
+        if data.len() as u64 != entry.size {
+            return Err(format!("expected {} bytes, got {}", entry.size, data.len()).into());
+        }
+        if crc32(&data) != entry.crc32 {
+            return Err("checksum mismatch".into());
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: FileMetadata = FileMetadata { mode: 0o640, mtime: 1_600_000_000 };
+
+    fn archive() -> (Vec<u8>, Vec<(&'static str, Vec<u8>)>) {
+        let files = vec![
+            ("empty", Vec::new()),
+            ("one", b"x".to_vec()),
+            ("bytes/all", (0..=255).collect()),
+            ("text/notes.txt", b"archives keep several files together. ".repeat(40)),
+        ];
+        let mut writer = ArchiveWriter::new();
+        for (path, data) in &files {
+            writer.add(path, data, METADATA, "lz77+huffman", &CodecOptions::default()).unwrap();
+        }
+        (writer.finish(), files)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (data, files) = archive();
+        let archive = Archive::parse(&data).unwrap();
+        assert_eq!(archive.entries().len(), files.len());
+        for (entry, (path, contents)) in archive.entries().iter().zip(&files) {
+            assert_eq!(entry.path, *path);
+            assert_eq!(entry.metadata, METADATA);
+            assert_eq!(archive.extract(entry, &CodecOptions::default()).unwrap(), *contents);
+        }
+        // Incompressible entries are stored as they are
+        assert_eq!(archive.entries()[1].algorithm, STORED);
+        assert_ne!(archive.entries()[3].algorithm, STORED);
+    }
+
+    #[test]
+    fn truncated_archives_fail() {
+        let (data, _) = archive();
+        for length in 0..data.len() {
+            assert!(Archive::parse(&data[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn corrupt_entry_fails_to_extract() {
+        let (mut data, _) = archive();
+        let entry = Archive::parse(&data).unwrap().entries()[3].clone();
+        data[entry.offset as usize + entry.compressed_size as usize / 2] ^= 0x10;
+        let archive = Archive::parse(&data).unwrap();
+        assert!(archive.extract(&entry, &CodecOptions::default()).is_err());
+    }
+
+    #[test]
+    fn unsafe_paths_are_rejected() {
+        for path in ["", "/etc/passwd", "../up", "a/../b", "a//b", "./a", "a\\b", "c:x"] {
+            assert!(validate_path(path).is_err(), "{:?}", path);
+            let mut writer = ArchiveWriter::new();
+            assert!(writer.add(path, b"x", METADATA, "rle", &CodecOptions::default()).is_err());
+        }
+        assert!(validate_path("dir/file.txt").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_bits_are_only_restored_on_request() {
+        let path = std::env::temp_dir().join(format!("compression_algorithm-mode-{}", std::process::id()));
+        fs::write(&path, b"#!/bin/sh\n").unwrap();
+        let metadata = FileMetadata { mode: 0o4755, mtime: 1_600_000_000 };
+        metadata.apply(&path).unwrap();
+        assert_eq!(FileMetadata::from_path(&path).unwrap().mode, 0o755);
+        metadata.apply_with_special_bits(&path).unwrap();
+        assert_eq!(FileMetadata::from_path(&path).unwrap().mode, 0o4755);
+        fs::remove_file(&path).unwrap();
+    }
+}
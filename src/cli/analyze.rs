@@ -0,0 +1,91 @@
+// `analyze` subcommand: describe uncompressed data to help pick an algorithm.
+//
+// Reports the byte histogram, order-0 and order-1 entropy, how Huffman coding
+// compares with the entropy bound, the distribution of byte runs and how much
+// of the data LZ77 finds repeated. The numbers come from
+// `compression::analyze`.
+
+use std::error::Error;
+
+use super::bench::json_string;
+use super::commands::read_input;
+use super::inspect::{byte_label, json_histogram, print_histogram};
+use super::AnalyzeArgs;
+use crate::compression::analysis::Analysis;
+use crate::compression::analyze as analyze_data;
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 { 0.0 } else { part as f64 / whole as f64 * 100.0 }
+}
+
+pub fn analyze(args: &AnalyzeArgs) -> Result<(), Box<dyn Error>> {
+    let data = read_input(&args.input)?;
+    let analysis = analyze_data(&data)?;
+    if args.json {
+        print_json(args, &analysis);
+    } else {
+        print_human(args, &analysis);
+    }
+    Ok(())
+}
+
+fn print_human(args: &AnalyzeArgs, analysis: &Analysis) {
+    let size = analysis.size;
+    outln!("File: {} ({} bytes)", args.input.display(), size);
+    outln!("Distinct bytes: {} of 256", analysis.distinct);
+    outln!("Entropy: order-0 {:.3} bits/byte, order-1 {:.3} bits/byte",
+           analysis.order0_entropy, analysis.order1_entropy);
+    outln!("Order-0 bound: {} bytes ({:.1}%)", analysis.entropy_bound, percent(analysis.entropy_bound, size));
+    outln!("Huffman: {} bytes of codes ({:.1}%), {} bytes with its code table ({:.1}%)",
+           analysis.huffman_bound, percent(analysis.huffman_bound, size),
+           analysis.huffman_size, percent(analysis.huffman_size, size));
+
+    let runs = &analysis.runs;
+    outln!("Runs: {} of 2+ bytes, longest {}, {} bytes in runs RLE can shorten ({:.1}%)",
+           runs.runs, runs.longest, runs.rle_bytes, percent(runs.rle_bytes, size));
+    if !runs.lengths.is_empty() {
+        outln!("  Run lengths:");
+        print_histogram(runs.lengths.iter().map(|(&start, &count)| {
+            let end = (start * 2 - 1).max(start);
+            (if end == start { start.to_string() } else { format!("{}-{}", start, end) }, count)
+        }));
+    }
+
+    let lz = &analysis.lz;
+    outln!("LZ77: {} matches, {} literals, {:.1}% of bytes repeated; output {} bytes ({:.1}%)",
+           lz.matches, lz.literals, lz.redundancy * 100.0, lz.compressed_size, percent(lz.compressed_size, size));
+
+    let mut bytes: Vec<(u8, usize)> = (0..=255u8)
+        .map(|byte| (byte, analysis.histogram[byte as usize]))
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    if bytes.is_empty() {
+        return;
+    }
+    bytes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let shown = if args.top == 0 { bytes.len() } else { args.top.min(bytes.len()) };
+    outln!("Most common bytes ({} of {}):", shown, bytes.len());
+    print_histogram(bytes[..shown].iter().map(|&(byte, count)| (byte_label(byte), count)));
+}
+
+fn print_json(args: &AnalyzeArgs, analysis: &Analysis) {
+    let histogram: Vec<String> = analysis.histogram.iter().map(usize::to_string).collect();
+    let runs = &analysis.runs;
+    let lz = &analysis.lz;
+    outln!("{{");
+    outln!("  \"file\": {},", json_string(&args.input.display().to_string()));
+    outln!("  \"size\": {},", analysis.size);
+    outln!("  \"distinct\": {},", analysis.distinct);
+    outln!("  \"order0_entropy\": {:.6},", analysis.order0_entropy);
+    outln!("  \"order1_entropy\": {:.6},", analysis.order1_entropy);
+    outln!("  \"entropy_bound\": {},", analysis.entropy_bound);
+    outln!("  \"huffman_bound\": {},", analysis.huffman_bound);
+    outln!("  \"huffman_size\": {},", analysis.huffman_size);
+    outln!("  \"runs\": {{\"runs\": {}, \"rle_bytes\": {}, \"longest\": {}, \"lengths\": {}}},",
+           runs.runs, runs.rle_bytes, runs.longest, json_histogram(&runs.lengths));
+    outln!("  \"lz77\": {{\"matches\": {}, \"literals\": {}, \"matched_bytes\": {}, \"redundancy\": {:.6}, \
+            \"compressed_size\": {}}},",
+           lz.matches, lz.literals, lz.matched_bytes, lz.redundancy, lz.compressed_size);
+    outln!("  \"histogram\": [{}]", histogram.join(", "));
+    outln!("}}");
+}
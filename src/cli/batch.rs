@@ -0,0 +1,343 @@
+// Batch mode for `compress` and `decompress`: many inputs, directories and glob
+// patterns, with gzip-style output naming.
+//
+// Each file F is written next to itself as F + suffix (or with the suffix
+// removed when decompressing), or under `--output-dir` with the layout of its
+// input directory mirrored. Originals are removed after a successful write
+// unless `--keep` is given, and existing outputs are only replaced with
+// `--force`. When decompressing, a file name recorded in the header replaces
+// the suffix-stripped one and the recorded permissions and modification time
+// are applied to the output. A summary table of every file is printed at the end.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::BatchArgs;
+use crate::container::FileInfo;
+
+/// Suffix added to compressed files unless `--suffix` is given
+pub const DEFAULT_SUFFIX: &str = ".pp";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Compress,
+    Decompress,
+}
+
+impl BatchArgs {
+    /// Whether any batch option was given
+    pub fn requested(&self) -> bool {
+        self.recursive || self.keep || self.force || self.suffix.is_some() || self.output_dir.is_some()
+    }
+
+    pub fn suffix(&self) -> &str {
+        self.suffix.as_deref().unwrap_or(DEFAULT_SUFFIX)
+    }
+}
+
+/// Whether the inputs need batch mode rather than a single file to `-o`/stdout
+pub fn is_batch(inputs: &[PathBuf], batch: &BatchArgs) -> bool {
+    batch.requested()
+        || inputs.len() > 1
+        || inputs.iter().any(|input| is_glob(&input.to_string_lossy()) || input.is_dir())
+}
+
+/// Whether `pattern` contains glob wildcards
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Match one path component against a pattern component with `*`, `?` and
+/// `[...]` classes (`[!...]` negates, `a-z` ranges)
+fn match_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_component(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && match_component(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(close) = pattern.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                return name.first() == Some(&'[') && match_component(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else { return false };
+            let (negate, class) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..close]),
+                _ => (false, &pattern[1..close]),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negate && match_component(&pattern[close + 1..], &name[1..])
+        }
+        Some(&literal) => name.first() == Some(&literal) && match_component(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Match path components against pattern components, where `**` matches any
+/// number of directories
+fn match_path(pattern: &[String], path: &[String]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(component) if component == "**" => {
+            (0..=path.len()).any(|skip| match_path(&pattern[1..], &path[skip..]))
+        }
+        Some(component) => {
+            let pattern_chars: Vec<char> = component.chars().collect();
+            !path.is_empty()
+                && match_component(&pattern_chars, &path[0].chars().collect::<Vec<_>>())
+                && match_path(&pattern[1..], &path[1..])
+        }
+    }
+}
+
+fn components(path: &Path) -> Vec<String> {
+    path.components().map(|component| component.as_os_str().to_string_lossy().into_owned()).collect()
+}
+
+/// Every regular file below `dir`, sorted
+pub fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut children = fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<Vec<_>, _>>()?;
+    children.sort();
+    for child in children {
+        let metadata = fs::symlink_metadata(&child)?;
+        if metadata.is_dir() {
+            walk(&child, files)?;
+        } else if metadata.is_file() {
+            files.push(child);
+        }
+    }
+    Ok(())
+}
+
+/// Compresses or decompresses the contents of the file at the given path,
+/// returning the output and, when decompressing, the name and metadata to
+/// give it
+pub type Process<'a> = dyn Fn(&Path, &[u8]) -> Result<(Vec<u8>, Option<FileInfo>), Box<dyn Error>> + 'a;
+
+/// A file to process and the directory its output path is mirrored from
+struct Input {
+    path: PathBuf,
+    base: PathBuf,
+    /// Named on the command line rather than found in a directory or by a pattern
+    explicit: bool,
+}
+
+/// What happened to one input
+enum Outcome {
+    Done { output: PathBuf, input_size: usize, output_size: usize },
+    Skipped(String),
+    /// Found by expansion with the wrong suffix for the direction; not reported
+    Ignored,
+    Failed(String),
+}
+
+/// Expand the command-line inputs into files. Directories need `--recursive`;
+/// glob patterns are matched from their first wildcard-free directory.
+fn expand_inputs(inputs: &[PathBuf], recursive: bool, rows: &mut Vec<(PathBuf, Outcome)>) -> Vec<Input> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let text = input.to_string_lossy();
+        if is_glob(&text) {
+            let parts = components(input);
+            let literal = parts.iter().take_while(|part| !is_glob(part)).count();
+            let base: PathBuf = parts[..literal].iter().collect();
+            let walk_from = if base.as_os_str().is_empty() { PathBuf::from(".") } else { base.clone() };
+
+            let mut found = Vec::new();
+            if let Err(e) = walk(&walk_from, &mut found) {
+                rows.push((input.clone(), Outcome::Failed(e.to_string())));
+                continue;
+            }
+            let before = files.len();
+            for path in found {
+                let relative = components(path.strip_prefix(&walk_from).unwrap_or(&path));
+                if match_path(&parts[literal..], &relative) {
+                    let path = if base.as_os_str().is_empty() { relative.iter().collect() } else { path };
+                    files.push(Input { path, base: base.clone(), explicit: false });
+                }
+            }
+            if files.len() == before {
+                rows.push((input.clone(), Outcome::Skipped("no files match the pattern".to_string())));
+            }
+        } else if input.is_dir() {
+            if !recursive {
+                rows.push((input.clone(), Outcome::Skipped("is a directory (use --recursive)".to_string())));
+                continue;
+            }
+            let mut found = Vec::new();
+            match walk(input, &mut found) {
+                Ok(()) => files.extend(found.into_iter().map(|path| Input { path, base: input.clone(), explicit: false })),
+                Err(e) => rows.push((input.clone(), Outcome::Failed(e.to_string()))),
+            }
+        } else {
+            let base = input.parent().map(Path::to_path_buf).unwrap_or_default();
+            files.push(Input { path: input.clone(), base, explicit: true });
+        }
+    }
+    files
+}
+
+/// Output path for `input`, or why it is skipped
+fn output_path(input: &Input, direction: Direction, batch: &BatchArgs) -> Result<PathBuf, String> {
+    let suffix = batch.suffix();
+    let name = input.path.file_name().ok_or("not a file")?.to_string_lossy().into_owned();
+    let output_name = match direction {
+        Direction::Compress if name.ends_with(suffix) => return Err(format!("already has {} suffix", suffix)),
+        Direction::Compress => format!("{}{}", name, suffix),
+        Direction::Decompress => match name.strip_suffix(suffix) {
+            Some(stem) if !stem.is_empty() => stem.to_string(),
+            _ => return Err(format!("unknown suffix (expected {})", suffix)),
+        },
+    };
+
+    let directory = match &batch.output_dir {
+        Some(output_dir) => {
+            let relative = input.path.strip_prefix(&input.base).unwrap_or(&input.path);
+            output_dir.join(relative.parent().unwrap_or(Path::new("")))
+        }
+        None => input.path.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    Ok(directory.join(output_name))
+}
+
+/// `output` renamed to the file name recorded in `file`, if it has a usable
+/// one. The rename may not overwrite the input or, without `--force`, an
+/// existing file.
+fn restored_path(output: &Path, file: Option<&FileInfo>, input: &Input, batch: &BatchArgs) -> Result<PathBuf, String> {
+    let Some(name) = file.and_then(FileInfo::safe_name) else {
+        return Ok(output.to_path_buf());
+    };
+    let restored = output.with_file_name(name);
+    if restored == output {
+        return Ok(restored);
+    }
+    if restored == input.path {
+        return Err(format!("recorded name '{}' would overwrite the input", name));
+    }
+    if restored.exists() && !batch.force {
+        return Err(format!("{} exists (use --force)", restored.display()));
+    }
+    Ok(restored)
+}
+
+fn process_one(
+    input: &Input,
+    direction: Direction,
+    batch: &BatchArgs,
+    process: &Process,
+) -> Outcome {
+    let output = match output_path(input, direction, batch) {
+        Ok(output) => output,
+        Err(_) if !input.explicit => return Outcome::Ignored,
+        Err(reason) => return Outcome::Skipped(reason),
+    };
+    if output.exists() && !batch.force {
+        return Outcome::Skipped(format!("{} exists (use --force)", output.display()));
+    }
+
+    let processed = fs::read(&input.path)
+        .map_err(Into::into)
+        .and_then(|data| process(&input.path, &data).map(|(result, file)| (data.len(), result, file)));
+    let (input_size, result, file) = match processed {
+        Ok(processed) => processed,
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    let output = match restored_path(&output, file.as_ref(), input, batch) {
+        Ok(output) => output,
+        Err(reason) => return Outcome::Skipped(reason),
+    };
+
+    let written = (|| {
+        if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&output, &result)?;
+        if let Some(file) = &file {
+            if let Err(e) = file.metadata.apply(&output) {
+                eprintln!("Warning: could not restore metadata of '{}': {}", output.display(), e);
+            }
+        }
+        if !batch.keep {
+            fs::remove_file(&input.path)?;
+        }
+        Ok::<_, Box<dyn Error>>(())
+    })();
+
+    match written {
+        Ok(()) => Outcome::Done { output, input_size, output_size: result.len() },
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+/// Run `process` over every input file and print a summary table. Fails if
+/// any file failed.
+pub fn run(
+    inputs: &[PathBuf],
+    direction: Direction,
+    batch: &BatchArgs,
+    process: &Process,
+) -> Result<(), Box<dyn Error>> {
+    if inputs.iter().any(|input| super::commands::is_stdio(input)) {
+        return Err("stdin cannot be combined with batch mode".into());
+    }
+
+    let mut rows = Vec::new();
+    let files = expand_inputs(inputs, batch.recursive, &mut rows);
+    for input in &files {
+        match process_one(input, direction, batch, process) {
+            Outcome::Ignored => {}
+            outcome => rows.push((input.path.clone(), outcome)),
+        }
+    }
+
+    print_summary(&rows);
+
+    let failed = rows.iter().filter(|(_, outcome)| matches!(outcome, Outcome::Failed(_))).count();
+    if failed > 0 {
+        return Err(format!("{} of {} files failed", failed, rows.len()).into());
+    }
+    Ok(())
+}
+
+fn print_summary(rows: &[(PathBuf, Outcome)]) {
+    outln!("{:<8} {:>12} {:>12} {:>7}  File", "Status", "In", "Out", "Ratio");
+    let (mut done, mut skipped, mut failed) = (0, 0, 0);
+    let (mut total_in, mut total_out) = (0usize, 0usize);
+
+    for (path, outcome) in rows {
+        match outcome {
+            Outcome::Done { output, input_size, output_size } => {
+                done += 1;
+                total_in += input_size;
+                total_out += output_size;
+                outln!("{:<8} {:>12} {:>12} {:>7}  {} -> {}",
+                       "ok", input_size, output_size, ratio(*input_size, *output_size),
+                       path.display(), output.display());
+            }
+            Outcome::Skipped(reason) => {
+                skipped += 1;
+                outln!("{:<8} {:>12} {:>12} {:>7}  {} ({})", "skipped", "-", "-", "-", path.display(), reason);
+            }
+            Outcome::Failed(reason) => {
+                failed += 1;
+                outln!("{:<8} {:>12} {:>12} {:>7}  {} ({})", "FAILED", "-", "-", "-", path.display(), reason);
+            }
+            Outcome::Ignored => {}
+        }
+    }
+
+    outln!("{} files: {} ok, {} skipped, {} failed; {} -> {} bytes ({})",
+           rows.len(), done, skipped, failed, total_in, total_out, ratio(total_in, total_out));
+}
+
+fn ratio(input: usize, output: usize) -> String {
+    if input == 0 { "-".to_string() } else { format!("{:.1}%", output as f64 / input as f64 * 100.0) }
+}
@@ -0,0 +1,268 @@
+// `bench` subcommand: run codecs over a set of files and compare them.
+//
+// Every codec sees every file. Each run is checked by decompressing and
+// comparing with the input, and the timings are the fastest of `--iterations`
+// runs. Results are totals over all files, so a directory of samples gives one
+// row per codec, printed as a table or as JSON/CSV for scripts.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::ValueEnum;
+
+use super::batch;
+use super::commands::{is_stdio, read_input};
+use super::{Algorithm, BenchArgs, ReportFormat};
+use crate::compression::{algorithm_from_spec_with_options, CodecOptions, CompressionAlgorithm};
+
+/// Parameter variants run with `--levels` in addition to each codec's default
+pub fn levels(algorithm: Algorithm) -> &'static [&'static str] {
+    match algorithm {
+        Algorithm::Lz77 => &["lz77:1", "lz77:9"],
+        Algorithm::Huffman => &["huffman:1", "huffman:9"],
+        Algorithm::Arith => &["arith:static", "arith:binary"],
+        Algorithm::Rans => &["rans:1", "rans:8"],
+        Algorithm::Ppm => &["ppm:2", "ppm:6"],
+        Algorithm::Lz78 => &["lz78:4096", "lz78:4096:freeze"],
+        Algorithm::Lzw => &["lzw:9", "lzw:12"],
+        Algorithm::Bwt => &["bwt:100000"],
+        Algorithm::Mtf => &["mtf:m1ff"],
+        Algorithm::Delta => &["delta:2", "delta:4"],
+        Algorithm::Xor => &["xor:4"],
+        _ => &[],
+    }
+}
+
+/// A file to benchmark on
+struct Sample {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+/// Totals of one codec over every sample
+struct Measurement {
+    algorithm: String,
+    input_size: usize,
+    compressed_size: usize,
+    compress_seconds: f64,
+    decompress_seconds: f64,
+    /// First compression error or roundtrip mismatch, with the file it happened on
+    error: Option<String>,
+}
+
+impl Measurement {
+    fn ratio(&self) -> f64 {
+        if self.input_size == 0 { 1.0 } else { self.compressed_size as f64 / self.input_size as f64 }
+    }
+
+    fn compress_throughput(&self) -> f64 {
+        throughput(self.input_size, self.compress_seconds)
+    }
+
+    fn decompress_throughput(&self) -> f64 {
+        throughput(self.input_size, self.decompress_seconds)
+    }
+}
+
+fn throughput(bytes: usize, seconds: f64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0) / seconds.max(1e-9)
+}
+
+/// Read every input, descending into directories
+fn load_samples(inputs: &[PathBuf]) -> Result<Vec<Sample>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if !is_stdio(input) && input.is_dir() {
+            batch::walk(input, &mut paths).map_err(|e| format!("cannot read '{}': {}", input.display(), e))?;
+        } else {
+            paths.push(input.clone());
+        }
+    }
+
+    paths.into_iter().map(|path| Ok(Sample { data: read_input(&path)?, path })).collect()
+}
+
+/// Run `codec` over one sample, returning its compressed size and the fastest
+/// compress and decompress times
+fn run_once(codec: &dyn CompressionAlgorithm, data: &[u8], iterations: u32) -> Result<(usize, f64, f64), String> {
+    let mut compressed = Vec::new();
+    let mut compress_seconds = f64::MAX;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        compressed = codec.compress(data).map_err(|e| format!("compression failed: {}", e))?;
+        compress_seconds = compress_seconds.min(start.elapsed().as_secs_f64());
+    }
+
+    let mut decompress_seconds = f64::MAX;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let decompressed = codec.decompress(&compressed).map_err(|e| format!("decompression failed: {}", e))?;
+        decompress_seconds = decompress_seconds.min(start.elapsed().as_secs_f64());
+        if decompressed != data {
+            return Err("output differs from input".to_string());
+        }
+    }
+
+    Ok((compressed.len(), compress_seconds, decompress_seconds))
+}
+
+fn measure(spec: &str, samples: &[Sample], iterations: u32) -> Measurement {
+    let mut measurement = Measurement {
+        algorithm: spec.to_string(),
+        input_size: 0,
+        compressed_size: 0,
+        compress_seconds: 0.0,
+        decompress_seconds: 0.0,
+        error: None,
+    };
+    let codec = match algorithm_from_spec_with_options(spec, &CodecOptions::default()) {
+        Ok(codec) => codec,
+        Err(e) => {
+            measurement.error = Some(e.to_string());
+            return measurement;
+        }
+    };
+
+    for sample in samples {
+        match run_once(codec.as_ref(), &sample.data, iterations) {
+            Ok((compressed_size, compress_seconds, decompress_seconds)) => {
+                measurement.input_size += sample.data.len();
+                measurement.compressed_size += compressed_size;
+                measurement.compress_seconds += compress_seconds;
+                measurement.decompress_seconds += decompress_seconds;
+            }
+            Err(e) => {
+                measurement.error = Some(format!("{}: {}", display_path(&sample.path), e));
+                break;
+            }
+        }
+    }
+    measurement
+}
+
+pub fn bench(args: &BenchArgs) -> Result<(), Box<dyn Error>> {
+    let samples = load_samples(&args.inputs)?;
+    let total: usize = samples.iter().map(|sample| sample.data.len()).sum();
+
+    let specs: Vec<String> = if args.algorithm.is_empty() {
+        let mut specs = Vec::new();
+        for &algorithm in Algorithm::value_variants() {
+            specs.push(algorithm.name().to_string());
+            if args.levels {
+                specs.extend(levels(algorithm).iter().map(|level| level.to_string()));
+            }
+        }
+        specs
+    } else {
+        args.algorithm.iter().map(ToString::to_string).collect()
+    };
+
+    let mut results = Vec::new();
+    for spec in &specs {
+        eprintln!("Benchmarking {}...", spec);
+        results.push(measure(spec, &samples, args.iterations));
+    }
+
+    match args.format {
+        ReportFormat::Table => print_table(&samples, total, args.iterations, &results),
+        ReportFormat::Json => print_json(&samples, args.iterations, &results),
+        ReportFormat::Csv => print_csv(&results),
+    }
+
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    if failed > 0 {
+        return Err(format!("{} of {} algorithms failed the roundtrip", failed, results.len()).into());
+    }
+    Ok(())
+}
+
+fn print_table(samples: &[Sample], total: usize, iterations: u32, results: &[Measurement]) {
+    outln!();
+    match samples {
+        [sample] => outln!("Benchmark of '{}' ({} bytes)", display_path(&sample.path), total),
+        _ => outln!("Benchmark of {} files ({} bytes)", samples.len(), total),
+    }
+    if iterations > 1 {
+        outln!("Fastest of {} runs per file", iterations);
+    }
+    outln!("{:<24} {:>12} {:>7} {:>12} {:>12}  Roundtrip", "Algorithm", "Compressed", "Ratio", "Comp MB/s", "Decomp MB/s");
+    for result in results {
+        match &result.error {
+            None => outln!("{:<24} {:>12} {:>6.1}% {:>12.2} {:>12.2}  ok",
+                           result.algorithm, result.compressed_size, result.ratio() * 100.0,
+                           result.compress_throughput(), result.decompress_throughput()),
+            Some(e) => outln!("{:<24} {:>12} {:>7} {:>12} {:>12}  FAILED: {}",
+                              result.algorithm, "-", "-", "-", "-", e),
+        }
+    }
+}
+
+/// Quote `text` as a JSON string
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn display_path(path: &Path) -> String {
+    if is_stdio(path) { "<stdin>".to_string() } else { path.display().to_string() }
+}
+
+fn print_json(samples: &[Sample], iterations: u32, results: &[Measurement]) {
+    outln!("{{");
+    outln!("  \"iterations\": {},", iterations);
+    outln!("  \"files\": [");
+    for (i, sample) in samples.iter().enumerate() {
+        let comma = if i + 1 < samples.len() { "," } else { "" };
+        outln!("    {{\"path\": {}, \"size\": {}}}{}", json_string(&display_path(&sample.path)), sample.data.len(), comma);
+    }
+    outln!("  ],");
+    outln!("  \"results\": [");
+    for (i, result) in results.iter().enumerate() {
+        let comma = if i + 1 < results.len() { "," } else { "" };
+        let fields = match &result.error {
+            None => format!("\"input_size\": {}, \"compressed_size\": {}, \"ratio\": {:.4}, \
+                             \"compress_mb_s\": {:.2}, \"decompress_mb_s\": {:.2}, \"roundtrip\": true",
+                            result.input_size, result.compressed_size, result.ratio(),
+                            result.compress_throughput(), result.decompress_throughput()),
+            Some(e) => format!("\"roundtrip\": false, \"error\": {}", json_string(e)),
+        };
+        outln!("    {{\"algorithm\": {}, {}}}{}", json_string(&result.algorithm), fields, comma);
+    }
+    outln!("  ]");
+    outln!("}}");
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn print_csv(results: &[Measurement]) {
+    outln!("algorithm,input_size,compressed_size,ratio,compress_mb_s,decompress_mb_s,roundtrip,error");
+    for result in results {
+        match &result.error {
+            None => outln!("{},{},{},{:.4},{:.2},{:.2},ok,",
+                           csv_field(&result.algorithm), result.input_size, result.compressed_size, result.ratio(),
+                           result.compress_throughput(), result.decompress_throughput()),
+            Some(e) => outln!("{},,,,,,failed,{}", csv_field(&result.algorithm), csv_field(e)),
+        }
+    }
+}
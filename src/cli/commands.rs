@@ -0,0 +1,684 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+use super::analyze::analyze;
+use super::batch::{self, Direction};
+use super::bench::bench;
+use super::inspect::inspect;
+use super::progress::progress_bar;
+use super::{
+    exit_on_write_error, AlgorithmChoice, AlgorithmSpec, BlockArgs, Cli, Command, CompressArgs, DecompressArgs,
+    InfoArgs, ListArgs, PackArgs, TestArgs, TrainArgs, UnpackArgs,
+};
+use crate::archive::{self, Archive, ArchiveWriter, FileMetadata};
+use crate::compression::dictionary::{MAX_SAMPLE_BYTES, MAX_TRAINING_BYTES};
+use crate::compression::{
+    algorithm_from_spec_with_options, auto, select_algorithm, lz77, lzw, parallel, seekable, train_dictionary, BlockParallel, CodecOptions,
+    CompressionAlgorithm, Seekable,
+};
+use crate::container::{self, FileInfo, Header};
+
+/// bzip2-style pipeline used for archive entries unless --algorithm is given
+pub const DEFAULT_ARCHIVE_ALGORITHM: &str = "bwt+mtf+zrle+arith";
+
+/// Path argument standing for stdin or stdout
+pub const STDIO: &str = "-";
+
+/// Run the subcommand selected on the command line
+pub fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match &cli.command {
+        Command::Compress(args) => compress(args),
+        Command::Decompress(args) => decompress(args),
+        Command::Bench(args) => bench(args),
+        Command::Info(args) => info(args),
+        Command::Inspect(args) => inspect(args),
+        Command::Analyze(args) => analyze(args),
+        Command::Test(args) => test(args),
+        Command::Train(args) => train(args),
+        Command::Pack(args) => pack(args),
+        Command::Unpack(args) => unpack(args),
+        Command::List(args) => list(args),
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    fs::read(path).map_err(|e| format!("cannot read '{}': {}", path.display(), e).into())
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    fs::write(path, data).map_err(|e| format!("cannot write '{}': {}", path.display(), e).into())
+}
+
+/// Whether a path argument is `-`, meaning stdin or stdout
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO
+}
+
+/// Read an input argument: a file, or stdin for `-`
+pub fn read_input(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    if is_stdio(path) {
+        let mut data = Vec::new();
+        io::stdin().lock().read_to_end(&mut data).map_err(|e| format!("cannot read stdin: {}", e))?;
+        Ok(data)
+    } else {
+        read_file(path)
+    }
+}
+
+/// Where a command writes its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    File(PathBuf),
+    Stdout,
+    /// No `-o` and stdout is a terminal: only report sizes
+    None,
+}
+
+impl Output {
+    /// `-o FILE`, `-o -` for stdout, or stdout by default when it is not a terminal
+    pub fn resolve(output: Option<&Path>) -> Self {
+        match output {
+            Some(path) if is_stdio(path) => Output::Stdout,
+            Some(path) => Output::File(path.to_path_buf()),
+            None if !io::stdout().is_terminal() => Output::Stdout,
+            None => Output::None,
+        }
+    }
+
+    /// Write `data`, returning a description of where it went for messages
+    fn write(&self, data: &[u8]) -> Result<Option<String>, Box<dyn Error>> {
+        match self {
+            Output::File(path) => {
+                write_file(path, data)?;
+                Ok(Some(format!("'{}'", path.display())))
+            }
+            Output::Stdout => {
+                let mut stdout = io::stdout().lock();
+                match stdout.write_all(data).and_then(|()| stdout.flush()) {
+                    // A reader that stopped early (`| head -c`) is not an error
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => exit_on_write_error(&e),
+                    result => result.map_err(|e| format!("cannot write stdout: {}", e))?,
+                }
+                Ok(Some("stdout".to_string()))
+            }
+            Output::None => Ok(None),
+        }
+    }
+}
+
+/// Codec options from the command line, loading the preset dictionary if one was given
+pub fn codec_options(dictionary: Option<&Path>) -> Result<CodecOptions, Box<dyn Error>> {
+    let mut options = CodecOptions::default();
+    if let Some(dictionary_file) = dictionary {
+        let dictionary = read_file(dictionary_file)?;
+        eprintln!("Preset dictionary: {} ({} bytes)", dictionary_file.display(), dictionary.len());
+        if dictionary.len() > lz77::WINDOW_SIZE {
+            eprintln!("Warning: only the last {} bytes of the dictionary fit in the window", lz77::WINDOW_SIZE);
+        }
+        options.dictionary = Some(dictionary);
+    }
+    Ok(options)
+}
+
+/// Compress `data` with `spec` and add the container header, recording `file`
+/// if given (native formats such as `.Z` are returned as the codec wrote them)
+pub fn compress_data(
+    data: &[u8],
+    spec: &str,
+    options: &CodecOptions,
+    blocks: &BlockArgs,
+    file: Option<FileInfo>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut header = Header::new(spec).with_checksum(data);
+    if let Some(file) = file {
+        header = header.with_file(file);
+    }
+    if blocks.is_split() && container::is_native_format(spec) {
+        return Err(format!("{} output cannot be split into blocks; use --threads 1", spec).into());
+    }
+
+    // Block files report progress per block rather than from inside each one
+    let compressor: Box<dyn CompressionAlgorithm> = if blocks.seekable {
+        let block_size = blocks.block_size.unwrap_or(seekable::DEFAULT_BLOCK_SIZE);
+        let mut codec = Seekable::new(block_codec(spec, options)?, block_size, blocks.threads);
+        if let Some(progress) = &options.progress {
+            codec = codec.with_progress(progress.clone());
+        }
+        header = header.with_seekable();
+        Box::new(codec)
+    } else if blocks.is_split() {
+        let block_size = blocks.block_size.unwrap_or(parallel::DEFAULT_BLOCK_SIZE);
+        let mut codec = BlockParallel::new(block_codec(spec, options)?, block_size, blocks.threads);
+        if let Some(progress) = &options.progress {
+            codec = codec.with_progress(progress.clone());
+        }
+        header = header.with_blocks();
+        Box::new(codec)
+    } else {
+        algorithm_from_spec_with_options(spec, options)?
+    };
+
+    let payload = compressor.compress(data)?;
+    if container::is_native_format(spec) {
+        Ok(payload)
+    } else {
+        container::wrap_with_header(&header, &payload)
+    }
+}
+
+/// Resolve `--algorithm` for `data`: a spec is used as given, `auto` tries the
+/// default candidates on samples of the data and keeps the smallest. Native
+/// formats are left out when the output is split into blocks.
+pub fn choose_algorithm(
+    choice: &AlgorithmChoice,
+    data: &[u8],
+    options: &CodecOptions,
+    split: bool,
+) -> Result<String, Box<dyn Error>> {
+    let AlgorithmChoice::Spec(spec) = choice else {
+        let candidates: Vec<&str> = auto::DEFAULT_CANDIDATES
+            .iter()
+            .copied()
+            .filter(|candidate| !(split && container::is_native_format(candidate)))
+            .collect();
+        let selection = select_algorithm(data, &candidates, options)?;
+        let rejected = selection.trials.iter().filter(|trial| trial.result.is_err()).count();
+        eprintln!("Auto-selected {} from {} candidates ({} rejected)", selection.algorithm, candidates.len(), rejected);
+        return Ok(selection.algorithm);
+    };
+    Ok(spec.to_string())
+}
+
+/// Split `data` into its header and payload. Files written by this tool record
+/// their algorithm in a container header; formats like .Z are recognised by
+/// their magic, anything else needs `fallback`.
+pub fn read_header<'a>(data: &'a [u8], fallback: Option<&str>) -> Result<(Header, &'a [u8]), Box<dyn Error>> {
+    if let Some((header, offset)) = Header::parse(data).map_err(|e| format!("invalid container header: {}", e))? {
+        return Ok((header, &data[offset..]));
+    }
+    match (container::detect_native_format(data), fallback) {
+        (Some(native), _) => Ok((Header::new(native), data)),
+        (None, Some(spec)) => Ok((Header::new(spec), data)),
+        (None, None) => Err("the file has no container header; pass --algorithm to say how it was compressed".into()),
+    }
+}
+
+/// The codec that reverses the payload described by `header`
+fn header_codec(
+    header: &Header,
+    options: &CodecOptions,
+    threads: usize,
+) -> Result<Box<dyn CompressionAlgorithm>, Box<dyn Error>> {
+    // Block sizes are read back from the stream
+    Ok(if header.seekable {
+        let mut codec = Seekable::new(block_codec(&header.algorithm, options)?, seekable::DEFAULT_BLOCK_SIZE, threads);
+        if let Some(progress) = &options.progress {
+            codec = codec.with_progress(progress.clone());
+        }
+        Box::new(codec)
+    } else if header.blocks {
+        let mut codec =
+            BlockParallel::new(block_codec(&header.algorithm, options)?, parallel::DEFAULT_BLOCK_SIZE, threads);
+        if let Some(progress) = &options.progress {
+            codec = codec.with_progress(progress.clone());
+        }
+        Box::new(codec)
+    } else {
+        algorithm_from_spec_with_options(&header.algorithm, options)?
+    })
+}
+
+/// The codec run on each block of a block file: `spec` without the progress
+/// callback, which the block wrapper reports to instead
+fn block_codec(spec: &str, options: &CodecOptions) -> Result<Box<dyn CompressionAlgorithm>, Box<dyn Error>> {
+    let options = CodecOptions { progress: None, ..options.clone() };
+    algorithm_from_spec_with_options(spec, &options)
+}
+
+/// Decompress a file's contents, returning its header alongside the data
+pub fn decompress_data(
+    data: &[u8],
+    fallback: Option<&str>,
+    options: &CodecOptions,
+    threads: usize,
+) -> Result<(Header, Vec<u8>), Box<dyn Error>> {
+    let (header, payload) = read_header(data, fallback)?;
+    let decompressed = header_codec(&header, options, threads)?.decompress(payload)?;
+    header.verify(&decompressed)?;
+    Ok((header, decompressed))
+}
+
+/// Decompress `compressed` in memory and check it reproduces `original`
+fn verify_roundtrip(
+    compressed: &[u8],
+    original: &[u8],
+    options: &CodecOptions,
+    threads: usize,
+) -> Result<(), Box<dyn Error>> {
+    let (_, decompressed) = decompress_data(compressed, None, options, threads)
+        .map_err(|e| format!("verification failed: {}", e))?;
+    if decompressed != original {
+        return Err("verification failed: decompressed output differs from the input".into());
+    }
+    Ok(())
+}
+
+/// Name and metadata to record for `input`: none for stdin or with `--no-preserve`
+fn input_file_info(input: &Path, no_preserve: bool) -> Result<Option<FileInfo>, Box<dyn Error>> {
+    if no_preserve || is_stdio(input) {
+        return Ok(None);
+    }
+    let file = FileInfo::from_path(input).map_err(|e| format!("cannot read metadata of '{}': {}", input.display(), e))?;
+    Ok(Some(file))
+}
+
+/// The single input of a non-batch command; `-o` only makes sense for one file
+fn single_input(inputs: &[PathBuf]) -> Result<&Path, Box<dyn Error>> {
+    match inputs {
+        [input] => Ok(input),
+        _ => Err("--output needs a single input; use --output-dir for several".into()),
+    }
+}
+
+pub fn compress(args: &CompressArgs) -> Result<(), Box<dyn Error>> {
+    let options = CodecOptions { level: args.level.level(), ..codec_options(args.dictionary.as_deref())? };
+    let split = args.blocks.is_split();
+
+    if args.output.is_none() && batch::is_batch(&args.inputs, &args.batch) {
+        return batch::run(&args.inputs, Direction::Compress, &args.batch, &|path, data| {
+            let algorithm = choose_algorithm(&args.algorithm, data, &options, split)?;
+            let file = input_file_info(path, args.no_preserve)?;
+            let compressed = compress_data(data, &algorithm, &options, &args.blocks, file)?;
+            if args.verify {
+                verify_roundtrip(&compressed, data, &options, args.blocks.threads)?;
+            }
+            Ok((compressed, None))
+        });
+    }
+
+    let output = Output::resolve(args.output.as_deref());
+    let input = single_input(&args.inputs)?;
+    let data = read_input(input)?;
+    let algorithm = choose_algorithm(&args.algorithm, &data, &options, split)?;
+    let file = input_file_info(input, args.no_preserve)?;
+    let options = CodecOptions { progress: progress_bar("Compressing"), ..options };
+
+    eprintln!("Starting {} compression on {} bytes of data", algorithm, data.len());
+    let compressed = compress_data(&data, &algorithm, &options, &args.blocks, file)
+        .map_err(|e| format!("compression failed: {}", e))?;
+    if args.verify {
+        verify_roundtrip(&compressed, &data, &options, args.blocks.threads)?;
+        eprintln!("Verified: output decompresses to the original {} bytes", data.len());
+    }
+
+    match output.write(&compressed)? {
+        Some(destination) => {
+            eprintln!("Compressed data saved to {}", destination);
+            eprintln!("Original size: {} bytes", data.len());
+            eprintln!("Compressed size: {} bytes", compressed.len());
+            let ratio = (compressed.len() as f64 / data.len() as f64) * 100.0;
+            eprintln!("Compression ratio: {:.1}%", ratio);
+            eprintln!("Space saved: {:.1}%", 100.0 - ratio);
+        }
+        None => {
+            eprintln!("No output file specified. Use -o to save compressed data.");
+            eprintln!("Compressed data size: {} bytes", compressed.len());
+        }
+    }
+    Ok(())
+}
+
+pub fn decompress(args: &DecompressArgs) -> Result<(), Box<dyn Error>> {
+    let options = codec_options(args.dictionary.as_deref())?;
+    let fallback = args.algorithm.as_ref().map(AlgorithmSpec::to_string);
+
+    if args.output.is_none() && args.range.is_none() && batch::is_batch(&args.inputs, &args.batch) {
+        return batch::run(&args.inputs, Direction::Decompress, &args.batch, &|_, data| {
+            let (header, decompressed) = decompress_data(data, fallback.as_deref(), &options, args.threads)?;
+            Ok((decompressed, header.file.filter(|_| !args.no_preserve)))
+        });
+    }
+
+    let output = Output::resolve(args.output.as_deref());
+    let input = single_input(&args.inputs)?;
+    if let Some(range) = &args.range {
+        return decompress_range(input, &output, range, &options);
+    }
+
+    let data = read_input(input)?;
+    let (header, payload) = read_header(&data, fallback.as_deref())?;
+    let output = match (&output, &header.file) {
+        (Output::None, Some(file)) if !args.no_preserve && !is_stdio(input) => {
+            recorded_output(input, file)?.unwrap_or(output)
+        }
+        _ => output,
+    };
+    let options = CodecOptions { progress: progress_bar("Decompressing"), ..options };
+
+    eprintln!("Starting {} decompression on {} bytes of data", header.algorithm, data.len());
+    let decompressed = header_codec(&header, &options, args.threads)?
+        .decompress(payload)
+        .map_err(|e| format!("decompression failed: {}", e))?;
+    header.verify(&decompressed)?;
+
+    match output.write(&decompressed)? {
+        Some(destination) => {
+            // An explicit -o name wins over the recorded one; the rest still applies
+            if let (Output::File(path), Some(file), false) = (&output, &header.file, args.no_preserve) {
+                if let Err(e) = file.metadata.apply(path) {
+                    eprintln!("Warning: could not restore metadata of '{}': {}", path.display(), e);
+                }
+            }
+            eprintln!("Decompressed data saved to {}", destination);
+            eprintln!("Compressed size: {} bytes", data.len());
+            eprintln!("Decompressed size: {} bytes", decompressed.len());
+        }
+        None => {
+            eprintln!("No output file specified. Use -o to save decompressed data.");
+            eprintln!("Decompressed data size: {} bytes", decompressed.len());
+        }
+    }
+    Ok(())
+}
+
+/// Without `-o`, a file with a recorded name is restored next to `input` under
+/// that name, like gunzip does; `None` when no usable name was recorded
+fn recorded_output(input: &Path, file: &FileInfo) -> Result<Option<Output>, Box<dyn Error>> {
+    let Some(name) = file.safe_name() else {
+        return Ok(None);
+    };
+    let restored = input.with_file_name(name);
+    if restored == input {
+        return Err(format!("recorded name '{}' would overwrite the input; use -o", name).into());
+    }
+    // `--force` would switch to batch mode, which also removes the input
+    if restored.exists() {
+        return Err(format!("{} exists; use -o to choose the output", restored.display()).into());
+    }
+    Ok(Some(Output::File(restored)))
+}
+
+/// Parse `START[:LEN]`
+fn parse_range(range: &str) -> Result<(u64, Option<u64>), Box<dyn Error>> {
+    let parsed = match range.split_once(':') {
+        Some((start, len)) => start.parse::<u64>().ok().zip(len.parse::<u64>().ok().map(Some)),
+        None => range.parse::<u64>().ok().map(|start| (start, None)),
+    };
+    parsed.ok_or_else(|| format!("invalid range '{}' (expected START or START:LEN)", range).into())
+}
+
+/// Decompress `START[:LEN]` from a seekable file, reading only the blocks it covers
+fn decompress_range(input: &Path, output: &Output, range: &str, options: &CodecOptions) -> Result<(), Box<dyn Error>> {
+    let (start, len) = parse_range(range)?;
+    if is_stdio(input) {
+        return Err("--range needs a seekable file, not stdin".into());
+    }
+    let file = fs::File::open(input).map_err(|e| format!("cannot read '{}': {}", input.display(), e))?;
+    let mut reader = container::open_seekable(file, options)
+        .map_err(|e| format!("cannot open '{}' for random access: {}", input.display(), e))?;
+
+    eprintln!("Seekable file: {} bytes in {} blocks", reader.len(), reader.index().len());
+    let mut data = Vec::new();
+    reader.seek(SeekFrom::Start(start))?;
+    match len {
+        Some(len) => reader.take(len).read_to_end(&mut data),
+        None => reader.read_to_end(&mut data),
+    }
+    .map_err(|e| format!("decompression failed: {}", e))?;
+
+    match output.write(&data)? {
+        Some(destination) => {
+            eprintln!("Decompressed bytes {}..{} saved to {}", start, start + data.len() as u64, destination);
+        }
+        None => {
+            eprintln!("No output file specified. Use -o to save decompressed data.");
+            eprintln!("Decompressed data size: {} bytes", data.len());
+        }
+    }
+    Ok(())
+}
+
+pub fn info(args: &InfoArgs) -> Result<(), Box<dyn Error>> {
+    let data = read_input(&args.input)?;
+    outln!("File: {} ({} bytes)", args.input.display(), data.len());
+
+    if archive::is_archive(&data) {
+        let archive = Archive::parse(&data)?;
+        let size: u64 = archive.entries().iter().map(|entry| entry.size).sum();
+        outln!("Format: Pied Piper archive");
+        outln!("Entries: {} ({} bytes uncompressed); use `list` to see them", archive.entries().len(), size);
+        return Ok(());
+    }
+
+    if let Some((header, offset)) = Header::parse(&data)? {
+        let payload = &data[offset..];
+        outln!("Format: Pied Piper container");
+        outln!("Algorithm: {}", header.algorithm);
+        outln!("Header: {} bytes, payload {} bytes", offset, payload.len());
+        if header.seekable {
+            let (length, index) = seekable::read_index(payload)?;
+            outln!("Layout: seekable, {} blocks, {} bytes uncompressed", index.len(), length);
+        } else if header.blocks {
+            let (block_size, count) = parallel::read_layout(payload)?;
+            outln!("Layout: {} independent blocks of up to {} bytes", count, block_size);
+        } else {
+            outln!("Layout: single stream");
+        }
+        match header.checksum {
+            Some(checksum) => outln!("Checksum: CRC-32 {:08x}", checksum),
+            None => outln!("Checksum: none"),
+        }
+        if let Some(file) = &header.file {
+            outln!("Original file: {} (mode {:o}, modified {} UTC)",
+                   if file.name.is_empty() { "(unnamed)" } else { &file.name },
+                   file.metadata.mode, format_mtime(file.metadata.mtime));
+        }
+        return Ok(());
+    }
+
+    if container::detect_native_format(&data) == Some("lzw") {
+        let flags = *data.get(2).ok_or("truncated .Z header")?;
+        outln!("Format: Unix compress (.Z)");
+        outln!("Algorithm: lzw");
+        outln!("Maximum code width: {} bits", flags & lzw::BITS_MASK);
+        outln!("Block mode: {}", if flags & lzw::BLOCK_MODE_FLAG != 0 { "yes" } else { "no" });
+        return Ok(());
+    }
+
+    outln!("Format: unknown (no container header); decompress it with --algorithm");
+    Ok(())
+}
+
+pub fn test(args: &TestArgs) -> Result<(), Box<dyn Error>> {
+    let options = codec_options(args.dictionary.as_deref())?;
+    let fallback = args.algorithm.as_ref().map(AlgorithmSpec::to_string);
+
+    let mut failed = 0;
+    for input in &args.inputs {
+        let result = read_input(input).and_then(|data| {
+            if archive::is_archive(&data) {
+                let archive = Archive::parse(&data)?;
+                let mut size = 0;
+                for entry in archive.entries() {
+                    archive.extract(entry, &options).map_err(|e| format!("{}: {}", entry.path, e))?;
+                    size += entry.size;
+                }
+                Ok(format!("archive, {} entries, {} bytes", archive.entries().len(), size))
+            } else {
+                let (header, decompressed) = decompress_data(&data, fallback.as_deref(), &options, 0)?;
+                let checksum = if header.checksum.is_some() { "checksum ok" } else { "no checksum" };
+                Ok(format!("{}, {} bytes, {}", header.algorithm, decompressed.len(), checksum))
+            }
+        });
+
+        match result {
+            Ok(summary) => outln!("{}: OK ({})", input.display(), summary),
+            Err(e) => {
+                outln!("{}: FAILED ({})", input.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} files failed", failed, args.inputs.len()).into());
+    }
+    Ok(())
+}
+
+pub fn train(args: &TrainArgs) -> Result<(), Box<dyn Error>> {
+    if args.size > lz77::WINDOW_SIZE {
+        return Err(format!("--size {} is larger than the {} byte LZ77 window", args.size, lz77::WINDOW_SIZE).into());
+    }
+    let samples = args.samples.iter().map(|path| read_file(path)).collect::<Result<Vec<_>, _>>()?;
+
+    let sample_bytes: usize = samples.iter().map(Vec::len).sum();
+    eprintln!("Training a {} byte dictionary from {} samples ({} bytes)", args.size, samples.len(), sample_bytes);
+    if sample_bytes > MAX_TRAINING_BYTES || samples.iter().any(|sample| sample.len() > MAX_SAMPLE_BYTES) {
+        eprintln!("Warning: only the first {} KiB of each sample and {} KiB in total are used",
+                 MAX_SAMPLE_BYTES / 1024, MAX_TRAINING_BYTES / 1024);
+    }
+
+    let dictionary = train_dictionary(&samples, args.size);
+    if dictionary.is_empty() {
+        return Err("no repeated content found in the samples; dictionary not written".into());
+    }
+
+    write_file(&args.output, &dictionary)?;
+    eprintln!("Dictionary saved to '{}' ({} bytes, id {:08x})",
+             args.output.display(), dictionary.len(), lz77::LZ77::dictionary_id(&dictionary));
+    Ok(())
+}
+
+/// Collect the regular files under `path`, named relative to `base` with '/' separators
+fn collect_files(path: &Path, base: &Path, files: &mut Vec<(PathBuf, String)>) -> std::io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        let mut children = fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<Vec<_>, _>>()?;
+        children.sort();
+        for child in children {
+            collect_files(&child, base, files)?;
+        }
+    } else if metadata.is_file() {
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        let name: Vec<String> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        files.push((path.to_path_buf(), name.join("/")));
+    } else {
+        eprintln!("Skipping '{}' (not a regular file)", path.display());
+    }
+    Ok(())
+}
+
+pub fn pack(args: &PackArgs) -> Result<(), Box<dyn Error>> {
+    let options = CodecOptions { level: args.level.level(), ..CodecOptions::default() };
+
+    // Entries are named from the last component of each input, like tar
+    let mut files = Vec::new();
+    for input in &args.inputs {
+        let base = match input.file_name() {
+            Some(_) => input.parent().unwrap_or(Path::new("")),
+            None => input,
+        };
+        collect_files(input, base, &mut files).map_err(|e| format!("cannot read '{}': {}", input.display(), e))?;
+    }
+
+    let mut writer = ArchiveWriter::new();
+    for (path, name) in &files {
+        let data = read_file(path)?;
+        let metadata = FileMetadata::from_path(path)?;
+        let algorithm = choose_algorithm(&args.algorithm, &data, &options, false)?;
+        let entry = writer
+            .add(name, &data, metadata, &algorithm, &options)
+            .map_err(|e| format!("cannot add '{}': {}", path.display(), e))?;
+        eprintln!("  {} ({} -> {} bytes, {})", entry.path, entry.size, entry.compressed_size, entry.algorithm);
+    }
+
+    let entries = writer.entries().len();
+    let original: u64 = writer.entries().iter().map(|entry| entry.size).sum();
+    let archive = writer.finish();
+    write_file(&args.output, &archive)?;
+    eprintln!("Packed {} files ({} bytes) into '{}' ({} bytes)", entries, original, args.output.display(), archive.len());
+    Ok(())
+}
+
+pub fn unpack(args: &UnpackArgs) -> Result<(), Box<dyn Error>> {
+    let options = CodecOptions::default();
+    let data = read_file(&args.archive)?;
+    let archive = Archive::parse(&data).map_err(|e| format!("invalid archive '{}': {}", args.archive.display(), e))?;
+    let destinations: Vec<PathBuf> = archive
+        .entries()
+        .iter()
+        .map(|entry| entry.path.split('/').fold(args.directory.clone(), |path, part| path.join(part)))
+        .collect();
+
+    // Check before extracting anything, so a refusal leaves no partial tree
+    if !args.force {
+        if let Some(existing) = destinations.iter().find(|destination| destination.exists()) {
+            return Err(format!("{} exists (use --force)", existing.display()).into());
+        }
+    }
+
+    for (entry, destination) in archive.entries().iter().zip(&destinations) {
+        let contents = archive.extract(entry, &options).map_err(|e| format!("cannot extract '{}': {}", entry.path, e))?;
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("cannot create '{}': {}", parent.display(), e))?;
+        }
+        write_file(destination, &contents)?;
+        let restored = if args.special_bits {
+            entry.metadata.apply_with_special_bits(destination)
+        } else {
+            entry.metadata.apply(destination)
+        };
+        if let Err(e) = restored {
+            eprintln!("Warning: could not restore metadata of '{}': {}", destination.display(), e);
+        }
+        eprintln!("  {}", entry.path);
+    }
+
+    eprintln!("Extracted {} files into '{}'", archive.entries().len(), args.directory.display());
+    Ok(())
+}
+
+pub fn list(args: &ListArgs) -> Result<(), Box<dyn Error>> {
+    let data = read_file(&args.archive)?;
+    let archive = Archive::parse(&data).map_err(|e| format!("invalid archive '{}': {}", args.archive.display(), e))?;
+
+    outln!("{:<6} {:>12} {:>12} {:>7}  {:<20} {:<16}  Path", "Mode", "Size", "Compressed", "Ratio", "Algorithm", "Modified (UTC)");
+    for entry in archive.entries() {
+        let ratio = if entry.size == 0 { 100.0 } else { entry.compressed_size as f64 / entry.size as f64 * 100.0 };
+        outln!("{:<6o} {:>12} {:>12} {:>6.1}%  {:<20} {:<16}  {}",
+               entry.metadata.mode, entry.size, entry.compressed_size, ratio,
+               entry.algorithm, format_mtime(entry.metadata.mtime), entry.path);
+    }
+
+    let size: u64 = archive.entries().iter().map(|entry| entry.size).sum();
+    outln!("{} files, {} bytes, archive {} bytes", archive.entries().len(), size, data.len());
+    Ok(())
+}
+
+/// Format seconds since the Unix epoch as "YYYY-MM-DD HH:MM" in UTC
+pub fn format_mtime(mtime: i64) -> String {
+    let days = mtime.div_euclid(86_400);
+    let seconds = mtime.rem_euclid(86_400);
+
+    // Civil date from a day count (Howard Hinnant's days_from_civil, inverted)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, seconds / 3600, seconds % 3600 / 60)
+}
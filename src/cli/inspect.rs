@@ -0,0 +1,371 @@
+// `inspect` subcommand: dump what the codecs wrote inside a compressed file.
+//
+// The container header is printed first, then the stages of the pipeline are
+// undone one at a time, last stage first. Before each stage is decoded its
+// stream is parsed: Huffman streams show their code table, LZ77 streams their
+// literal/match counts with length and distance histograms, and RLE streams
+// their runs. Files split into blocks are inspected one block at a time.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use super::bench::json_string;
+use super::commands::{codec_options, format_mtime, read_input};
+use super::InspectArgs;
+use crate::archive;
+use crate::compression::huffman::HuffmanCoding;
+use crate::compression::lz77::{TokenStats, LZ77};
+use crate::compression::rle::{RunLengthEncoding, RunStats};
+use crate::compression::{algorithm_from_name_with_options, lzw, parallel, pipeline::STAGE_SEPARATOR, seekable};
+use crate::container::{self, Header};
+
+/// Widest histogram bar in the human report
+const BAR_WIDTH: usize = 40;
+
+/// What a stage's stream says about how it was coded
+enum Details {
+    None,
+    Huffman {
+        original_size: usize,
+        /// Number of blocks with their own code table; only the first is listed
+        tables: usize,
+        /// (byte, code length, code, occurrences in the first block's decoded data)
+        codes: Vec<(u8, u8, u64, usize)>,
+    },
+    Lz77(TokenStats),
+    Rle(RunStats),
+}
+
+struct StageReport {
+    algorithm: String,
+    stream_size: usize,
+    /// Size after undoing the stage, or why that failed
+    decoded: Result<usize, String>,
+    details: Result<Details, String>,
+}
+
+/// Where the inspected stream came from
+struct Layout {
+    description: String,
+    /// Index of the inspected block, for files split into blocks
+    block: Option<usize>,
+}
+
+fn parse_details(base: &str, stream: &[u8], decoded: Option<&[u8]>) -> Result<Details, Box<dyn Error>> {
+    Ok(match base {
+        "huffman" if !stream.is_empty() => {
+            let original_size = u32::from_le_bytes(stream.get(..4).ok_or("Huffman data too short")?.try_into()?) as usize;
+            let blocks = HuffmanCoding::blocks(stream)?;
+            let first = blocks.first().ok_or("Huffman stream has no blocks")?;
+            let (block_size, lengths, _) = HuffmanCoding::read_code_table(first)?;
+            let mut counts = [0usize; 256];
+            for &byte in decoded.unwrap_or_default().iter().take(block_size) {
+                counts[byte as usize] += 1;
+            }
+            let codes = HuffmanCoding::canonical_codes(&lengths);
+            let mut table: Vec<(u8, u8, u64, usize)> = (0..=255u8)
+                .filter(|&byte| lengths[byte as usize] > 0)
+                .map(|byte| (byte, lengths[byte as usize], codes[byte as usize].0, counts[byte as usize]))
+                .collect();
+            table.sort_by_key(|&(byte, length, _, _)| (length, byte));
+            Details::Huffman { original_size, tables: blocks.len(), codes: table }
+        }
+        "lz77" => Details::Lz77(LZ77::token_stats(stream)?),
+        "rle" => Details::Rle(RunLengthEncoding::run_stats(stream)?),
+        _ => Details::None,
+    })
+}
+
+/// Undo the stages of `spec` on `stream`, last first, reporting each
+fn inspect_stages(spec: &str, mut stream: Vec<u8>, args: &InspectArgs) -> Result<Vec<StageReport>, Box<dyn Error>> {
+    let options = codec_options(args.dictionary.as_deref())?;
+    let mut reports = Vec::new();
+    for name in spec.split(STAGE_SEPARATOR).rev() {
+        let base = name.split(':').next().unwrap_or(name);
+        let codec = algorithm_from_name_with_options(name, &options)?;
+        let decoded = codec.decompress(&stream).map_err(|e| e.to_string());
+        let details = parse_details(base, &stream, decoded.as_deref().ok()).map_err(|e| e.to_string());
+
+        reports.push(StageReport {
+            algorithm: name.to_string(),
+            stream_size: stream.len(),
+            decoded: decoded.as_ref().map(Vec::len).map_err(Clone::clone),
+            details,
+        });
+        match decoded {
+            Ok(decoded) => stream = decoded,
+            Err(_) => break,
+        }
+    }
+    Ok(reports)
+}
+
+/// Pick the stream to inspect out of the container payload
+fn select_stream<'a>(header: &Header, payload: &'a [u8], block: usize) -> Result<(Layout, &'a [u8]), Box<dyn Error>> {
+    let (kind, blocks): (&str, Vec<&[u8]>) = if header.seekable {
+        let (_, index) = seekable::read_index(payload)?;
+        let blocks = index
+            .iter()
+            .map(|entry| {
+                let start = entry.compressed_offset as usize;
+                &payload[start..start + entry.compressed_size as usize]
+            })
+            .collect();
+        ("seekable", blocks)
+    } else if header.blocks {
+        ("independent blocks", parallel::read_blocks(payload)?)
+    } else {
+        let layout = Layout { description: "single stream".to_string(), block: None };
+        return Ok((layout, payload));
+    };
+
+    let stream = *blocks
+        .get(block)
+        .ok_or_else(|| format!("block {} does not exist (the file has {} blocks)", block, blocks.len()))?;
+    let layout = Layout {
+        description: format!("{}, {} blocks", kind, blocks.len()),
+        block: Some(block),
+    };
+    Ok((layout, stream))
+}
+
+pub fn inspect(args: &InspectArgs) -> Result<(), Box<dyn Error>> {
+    let data = read_input(&args.input)?;
+    if archive::is_archive(&data) {
+        return Err("inspect works on single compressed files; use `list` for archives".into());
+    }
+
+    if container::detect_native_format(&data) == Some("lzw") {
+        let flags = *data.get(2).ok_or("truncated .Z header")?;
+        let fields = vec![
+            ("format", Value::Text("Unix compress (.Z)".to_string())),
+            ("algorithm", Value::Text("lzw".to_string())),
+            ("max_code_bits", Value::Number((flags & lzw::BITS_MASK) as u64)),
+            ("block_mode", Value::Bool(flags & lzw::BLOCK_MODE_FLAG != 0)),
+        ];
+        // A .Z stream has nothing to show beyond its header
+        print_report(args, data.len(), &fields, &[]);
+        return Ok(());
+    }
+
+    let (header, offset) = match Header::parse(&data)? {
+        Some(parsed) => parsed,
+        None => match &args.algorithm {
+            Some(spec) => (Header::new(spec.to_string()), 0),
+            None => return Err("the file has no container header; pass --algorithm to say how it was compressed".into()),
+        },
+    };
+    let payload = &data[offset..];
+    let (layout, stream) = select_stream(&header, payload, args.block)?;
+
+    let mut fields = vec![
+        ("format", Value::Text(if offset > 0 { "Pied Piper container" } else { "raw stream" }.to_string())),
+        ("algorithm", Value::Text(header.algorithm.clone())),
+        ("header_size", Value::Number(offset as u64)),
+        ("payload_size", Value::Number(payload.len() as u64)),
+        ("layout", Value::Text(layout.description)),
+        ("checksum", match header.checksum {
+            Some(checksum) => Value::Text(format!("{:08x}", checksum)),
+            None => Value::Null,
+        }),
+    ];
+    if let Some(file) = &header.file {
+        fields.push(("original_name", Value::Text(file.name.clone())));
+        fields.push(("mode", Value::Text(format!("{:o}", file.metadata.mode))));
+        fields.push(("modified", Value::Text(format!("{} UTC", format_mtime(file.metadata.mtime)))));
+    }
+    if let Some(block) = layout.block {
+        fields.push(("block", Value::Number(block as u64)));
+        fields.push(("block_compressed_size", Value::Number(stream.len() as u64)));
+    }
+
+    let stages = inspect_stages(&header.algorithm, stream.to_vec(), args)?;
+    print_report(args, data.len(), &fields, &stages);
+
+    match stages.iter().find_map(|stage| stage.decoded.as_ref().err()) {
+        Some(e) => Err(format!("decoding stopped: {}", e).into()),
+        None => Ok(()),
+    }
+}
+
+/// A header field value
+enum Value {
+    Text(String),
+    Number(u64),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    fn json(&self) -> String {
+        match self {
+            Value::Text(text) => json_string(text),
+            Value::Number(number) => number.to_string(),
+            Value::Bool(flag) => flag.to_string(),
+            Value::Null => "null".to_string(),
+        }
+    }
+
+    fn human(&self) -> String {
+        match self {
+            Value::Text(text) => text.clone(),
+            Value::Number(number) => number.to_string(),
+            Value::Bool(flag) => if *flag { "yes" } else { "no" }.to_string(),
+            Value::Null => "none".to_string(),
+        }
+    }
+}
+
+fn print_report(args: &InspectArgs, size: usize, fields: &[(&str, Value)], stages: &[StageReport]) {
+    if args.json {
+        print_json(args, size, fields, stages);
+    } else {
+        print_human(args, size, fields, stages);
+    }
+}
+
+fn print_human(args: &InspectArgs, size: usize, fields: &[(&str, Value)], stages: &[StageReport]) {
+    outln!("File: {} ({} bytes)", args.input.display(), size);
+    for (name, value) in fields {
+        let mut label = name.replace('_', " ");
+        label[..1].make_ascii_uppercase();
+        outln!("{}: {}", label, value.human());
+    }
+
+    for (i, stage) in stages.iter().enumerate() {
+        outln!();
+        let decoded = match &stage.decoded {
+            Ok(size) => format!("{} -> {} bytes", stage.stream_size, size),
+            Err(e) => format!("{} bytes, FAILED to decode: {}", stage.stream_size, e),
+        };
+        outln!("Stage {} of {}: {} ({})", stages.len() - i, stages.len(), stage.algorithm, decoded);
+        match &stage.details {
+            Ok(details) => print_details(details),
+            Err(e) => outln!("  Stream could not be parsed: {}", e),
+        }
+    }
+}
+
+fn print_details(details: &Details) {
+    match details {
+        Details::None => {}
+        Details::Huffman { original_size, tables, codes } => {
+            let total: usize = codes.iter().map(|&(_, _, _, count)| count).sum();
+            let bits: usize = codes.iter().map(|&(_, length, _, count)| length as usize * count).sum();
+            outln!("  Original size: {} bytes", original_size);
+            if *tables > 1 {
+                outln!("  Code tables: {} (the first block's is shown)", tables);
+            }
+            outln!("  Symbols: {}", codes.len());
+            if total > 0 {
+                outln!("  Average code length: {:.3} bits per byte", bits as f64 / total as f64);
+            }
+            outln!("  {:<6} {:>6} {:>10}  Code", "Byte", "Length", "Count");
+            for &(byte, length, code, count) in codes {
+                outln!("  {:<6} {:>6} {:>10}  {:0width$b}", byte_label(byte), length, count, code, width = length as usize);
+            }
+        }
+        Details::Lz77(stats) => {
+            outln!("  Original size: {} bytes", stats.original_size);
+            if let Some(id) = stats.dictionary_id {
+                outln!("  Preset dictionary: {:08x}", id);
+            }
+            let tokens = stats.literals + stats.matches;
+            outln!("  Tokens: {} ({} literals, {} matches)", tokens, stats.literals, stats.matches);
+            if stats.matches > 0 {
+                outln!("  Matched bytes: {} (average match length {:.2})",
+                       stats.matched_bytes, stats.matched_bytes as f64 / stats.matches as f64);
+                outln!("  Match lengths:");
+                print_histogram(stats.lengths.iter().map(|(&length, &count)| (length.to_string(), count)));
+                outln!("  Match distances:");
+                print_histogram(stats.distances.iter().map(|(&start, &count)| {
+                    let end = (start as u32 * 2).saturating_sub(1).max(start as u32);
+                    (format!("{}-{}", start, end), count)
+                }));
+            }
+        }
+        Details::Rle(stats) => {
+            outln!("  Literals: {}, escaped 0xFF bytes: {}", stats.literals, stats.escapes);
+            outln!("  Runs: {} covering {} bytes", stats.runs, stats.run_bytes);
+            if stats.runs > 0 {
+                outln!("  Run lengths:");
+                print_histogram(stats.lengths.iter().map(|(&length, &count)| (length.to_string(), count)));
+            }
+        }
+    }
+}
+
+pub fn byte_label(byte: u8) -> String {
+    if byte.is_ascii_graphic() { format!("'{}'", byte as char) } else { format!("0x{:02X}", byte) }
+}
+
+pub fn print_histogram(buckets: impl Iterator<Item = (String, usize)>) {
+    let buckets: Vec<(String, usize)> = buckets.collect();
+    let max = buckets.iter().map(|&(_, count)| count).max().unwrap_or(1).max(1);
+    for (label, count) in buckets {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max));
+        outln!("    {:>11} {:>10}  {}", label, count, bar);
+    }
+}
+
+pub fn json_histogram<K: ToString>(histogram: &BTreeMap<K, usize>) -> String {
+    let entries: Vec<String> = histogram.iter().map(|(key, count)| format!("{}: {}", json_string(&key.to_string()), count)).collect();
+    format!("{{{}}}", entries.join(", "))
+}
+
+fn details_json(details: &Details) -> Option<(&'static str, String)> {
+    Some(match details {
+        Details::None => return None,
+        Details::Huffman { original_size, tables, codes } => {
+            let codes: Vec<String> = codes
+                .iter()
+                .map(|&(byte, length, code, count)| {
+                    format!("{{\"byte\": {}, \"length\": {}, \"code\": \"{:0width$b}\", \"count\": {}}}",
+                            byte, length, code, count, width = length as usize)
+                })
+                .collect();
+            ("huffman", format!("{{\"original_size\": {}, \"tables\": {}, \"codes\": [{}]}}",
+                                original_size, tables, codes.join(", ")))
+        }
+        Details::Lz77(stats) => ("lz77", format!(
+            "{{\"original_size\": {}, \"dictionary_id\": {}, \"literals\": {}, \"matches\": {}, \"matched_bytes\": {}, \
+             \"lengths\": {}, \"distances\": {}}}",
+            stats.original_size,
+            stats.dictionary_id.map_or("null".to_string(), |id| json_string(&format!("{:08x}", id))),
+            stats.literals, stats.matches, stats.matched_bytes,
+            json_histogram(&stats.lengths), json_histogram(&stats.distances),
+        )),
+        Details::Rle(stats) => ("rle", format!(
+            "{{\"literals\": {}, \"escapes\": {}, \"runs\": {}, \"run_bytes\": {}, \"lengths\": {}}}",
+            stats.literals, stats.escapes, stats.runs, stats.run_bytes, json_histogram(&stats.lengths),
+        )),
+    })
+}
+
+fn print_json(args: &InspectArgs, size: usize, fields: &[(&str, Value)], stages: &[StageReport]) {
+    outln!("{{");
+    outln!("  \"file\": {},", json_string(&args.input.display().to_string()));
+    outln!("  \"size\": {},", size);
+    for (name, value) in fields {
+        outln!("  {}: {},", json_string(name), value.json());
+    }
+    outln!("  \"stages\": [");
+    for (i, stage) in stages.iter().enumerate() {
+        let mut members = vec![
+            format!("\"algorithm\": {}", json_string(&stage.algorithm)),
+            format!("\"stream_size\": {}", stage.stream_size),
+        ];
+        match &stage.decoded {
+            Ok(size) => members.push(format!("\"decoded_size\": {}", size)),
+            Err(e) => members.push(format!("\"error\": {}", json_string(e))),
+        }
+        match &stage.details {
+            Ok(details) => members.extend(details_json(details).map(|(name, json)| format!("\"{}\": {}", name, json))),
+            Err(e) => members.push(format!("\"parse_error\": {}", json_string(e))),
+        }
+        let comma = if i + 1 < stages.len() { "," } else { "" };
+        outln!("    {{{}}}{}", members.join(", "), comma);
+    }
+    outln!("  ]");
+    outln!("}}");
+}
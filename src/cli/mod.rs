@@ -0,0 +1,539 @@
+// Command-line interface of the `compression_algorithm` binary.
+//
+// The argument types are clap derive structs and every subcommand is a plain
+// function returning a `Result`, so the binary only parses arguments and maps
+// errors to an exit code, and the commands can be driven from tests or other
+// tools without spawning a process.
+
+/// `println!` for reports on stdout, via `print_line`
+macro_rules! outln {
+    () => {
+        $crate::cli::print_line(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::cli::print_line(format_args!($($arg)*))
+    };
+}
+
+pub mod analyze;
+pub mod batch;
+pub mod bench;
+pub mod commands;
+pub mod inspect;
+pub mod progress;
+
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::compression::{algorithm_from_spec, auto::AUTO, pipeline::STAGE_SEPARATOR, CompressionLevel};
+
+pub use commands::run;
+
+/// Write one line of a report to locked stdout. If the reader has gone away
+/// (e.g. `| head`) nobody is left to report to, so the process exits
+/// successfully rather than panicking as `println!` does.
+pub fn print_line(args: fmt::Arguments) {
+    let mut stdout = io::stdout().lock();
+    if let Err(e) = writeln!(stdout, "{}", args) {
+        exit_on_write_error(&e);
+    }
+}
+
+/// End the process after a failed write to stdout: quietly for a closed pipe,
+/// with an error otherwise
+pub fn exit_on_write_error(error: &io::Error) -> ! {
+    if error.kind() == io::ErrorKind::BrokenPipe {
+        std::process::exit(0);
+    }
+    eprintln!("Error: cannot write stdout: {}", error);
+    std::process::exit(EXIT_FAILURE as i32);
+}
+
+/// Exit code for a failed command (clap uses 2 for usage errors)
+pub const EXIT_FAILURE: u8 = 1;
+
+#[derive(Debug, Parser)]
+#[command(name = "compression_algorithm", version, about = "A compression algorithm implementation")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Compress a file
+    Compress(CompressArgs),
+    /// Decompress a file written by `compress` (or a .Z file)
+    Decompress(DecompressArgs),
+    /// Compare codecs on files or directories: ratio, throughput and roundtrip
+    Bench(BenchArgs),
+    /// Show how a compressed file was written
+    Info(InfoArgs),
+    /// Dump the internals of a compressed file: header, Huffman codes, LZ77 tokens, RLE runs
+    Inspect(InspectArgs),
+    /// Describe uncompressed data: byte histogram, entropy, Huffman bound, runs and LZ77 redundancy
+    Analyze(AnalyzeArgs),
+    /// Check that compressed files decompress and match their checksums, writing nothing
+    Test(TestArgs),
+    /// Train a preset LZ77 dictionary from sample files
+    Train(TrainArgs),
+    /// Pack files and directories into a Pied Piper archive
+    Pack(PackArgs),
+    /// Extract every file from a Pied Piper archive
+    Unpack(UnpackArgs),
+    /// List the entries of a Pied Piper archive
+    List(ListArgs),
+}
+
+/// The single-stage codecs that can appear in an algorithm spec
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    Huffman,
+    Arith,
+    Rans,
+    Tans,
+    Ppm,
+    Lz77,
+    Lz78,
+    Lzw,
+    Rle,
+    Bwt,
+    Mtf,
+    Zrle,
+    Delta,
+    Xor,
+}
+
+impl Algorithm {
+    /// Registry name of the codec
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Huffman => "huffman",
+            Algorithm::Arith => "arith",
+            Algorithm::Rans => "rans",
+            Algorithm::Tans => "tans",
+            Algorithm::Ppm => "ppm",
+            Algorithm::Lz77 => "lz77",
+            Algorithm::Lz78 => "lz78",
+            Algorithm::Lzw => "lzw",
+            Algorithm::Rle => "rle",
+            Algorithm::Bwt => "bwt",
+            Algorithm::Mtf => "mtf",
+            Algorithm::Zrle => "zrle",
+            Algorithm::Delta => "delta",
+            Algorithm::Xor => "xor",
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How a report is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Aligned columns for reading in a terminal
+    Table,
+    Json,
+    Csv,
+}
+
+/// One stage of a spec: a codec and its optional `:` parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stage {
+    pub algorithm: Algorithm,
+    pub param: Option<String>,
+}
+
+/// A parsed `--algorithm` value such as `lz77`, `bwt:100000` or
+/// `bwt+mtf+zrle+huffman`. Parsing checks every stage name and parameter, so a
+/// bad spec is reported as a usage error before any file is touched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlgorithmSpec {
+    pub stages: Vec<Stage>,
+}
+
+impl AlgorithmSpec {
+    pub fn single(algorithm: Algorithm) -> Self {
+        AlgorithmSpec { stages: vec![Stage { algorithm, param: None }] }
+    }
+}
+
+impl FromStr for AlgorithmSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut stages = Vec::new();
+        for stage in spec.split(STAGE_SEPARATOR) {
+            let (name, param) = match stage.split_once(':') {
+                Some((name, param)) => (name, Some(param.to_string())),
+                None => (stage, None),
+            };
+            let algorithm = Algorithm::from_str(name.trim(), true).map_err(|_| {
+                let names: Vec<&str> = Algorithm::value_variants().iter().map(|a| a.name()).collect();
+                format!("unknown algorithm '{}' (expected one of: {})", name, names.join(", "))
+            })?;
+            stages.push(Stage { algorithm, param });
+        }
+
+        let parsed = AlgorithmSpec { stages };
+        algorithm_from_spec(&parsed.to_string()).map_err(|e| e.to_string())?;
+        Ok(parsed)
+    }
+}
+
+impl fmt::Display for AlgorithmSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", STAGE_SEPARATOR)?;
+            }
+            write!(f, "{}", stage.algorithm)?;
+            if let Some(param) = &stage.param {
+                write!(f, ":{}", param)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `--algorithm` for commands that write compressed data: a spec, or `auto` to
+/// try several codecs on each input and keep the one with the smallest output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlgorithmChoice {
+    Auto,
+    Spec(AlgorithmSpec),
+}
+
+impl FromStr for AlgorithmChoice {
+    type Err = String;
+
+    fn from_str(choice: &str) -> Result<Self, Self::Err> {
+        if choice.trim().eq_ignore_ascii_case(AUTO) {
+            Ok(AlgorithmChoice::Auto)
+        } else {
+            choice.parse().map(AlgorithmChoice::Spec)
+        }
+    }
+}
+
+impl fmt::Display for AlgorithmChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlgorithmChoice::Auto => f.write_str(AUTO),
+            AlgorithmChoice::Spec(spec) => spec.fmt(f),
+        }
+    }
+}
+
+/// Options for splitting the input into independently compressed blocks
+#[derive(Debug, Clone, Args)]
+pub struct BlockArgs {
+    /// Compress independent blocks on N threads (0 = all cores)
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    pub threads: usize,
+
+    /// Block size for multi-threaded (default 1048576) or seekable (default 65536) compression
+    #[arg(short, long, value_name = "BYTES")]
+    pub block_size: Option<usize>,
+
+    /// Write a block index so byte ranges can be decompressed without reading the whole file
+    #[arg(long)]
+    pub seekable: bool,
+}
+
+impl BlockArgs {
+    /// Whether the input is split into blocks: asked for seekable output, more
+    /// than one thread or a block size
+    pub fn is_split(&self) -> bool {
+        self.seekable || self.threads != 1 || self.block_size.is_some()
+    }
+}
+
+/// Compression level, given gzip-style as `-1` ... `-9` or as `--level N`
+#[derive(Debug, Clone, Args)]
+#[group(multiple = false)]
+pub struct LevelArgs {
+    /// Compression level for LZ77 and Huffman stages, from 1 (fastest) to 9 (best ratio) [default: 6]
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u8).range(1..=9))]
+    pub level: Option<u8>,
+
+    /// Compress faster (same as --level 1; -2 ... -8 pick the levels in between)
+    #[arg(short = '1', long)]
+    pub fast: bool,
+    #[arg(short = '2', hide = true)]
+    pub level2: bool,
+    #[arg(short = '3', hide = true)]
+    pub level3: bool,
+    #[arg(short = '4', hide = true)]
+    pub level4: bool,
+    #[arg(short = '5', hide = true)]
+    pub level5: bool,
+    #[arg(short = '6', hide = true)]
+    pub level6: bool,
+    #[arg(short = '7', hide = true)]
+    pub level7: bool,
+    #[arg(short = '8', hide = true)]
+    pub level8: bool,
+
+    /// Compress better (same as --level 9)
+    #[arg(short = '9', long)]
+    pub best: bool,
+}
+
+impl LevelArgs {
+    pub fn level(&self) -> CompressionLevel {
+        let flags = [
+            self.fast, self.level2, self.level3, self.level4, self.level5, self.level6, self.level7, self.level8,
+            self.best,
+        ];
+        let level = flags.iter().position(|&set| set).map(|i| i as u8 + 1).or(self.level);
+        level.and_then(|level| CompressionLevel::new(level).ok()).unwrap_or_default()
+    }
+}
+
+/// Options for processing many files at once. Batch mode is used with several
+/// inputs, a directory or glob pattern, or any of these options.
+#[derive(Debug, Clone, Args)]
+pub struct BatchArgs {
+    /// Process directories recursively
+    #[arg(short = 'R', long)]
+    pub recursive: bool,
+
+    /// Keep the input files instead of deleting them
+    #[arg(short, long)]
+    pub keep: bool,
+
+    /// Overwrite existing output files
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Suffix added to compressed files and removed on decompress (default .pp)
+    #[arg(short = 'S', long, value_name = "SUFFIX")]
+    pub suffix: Option<String>,
+
+    /// Write outputs under this directory, mirroring the input directory layout
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CompressArgs {
+    /// Files to compress, '-' for stdin, or directories and glob patterns (e.g. 'logs/**/*.log') for batch mode
+    #[arg(value_name = "INPUT", default_value = commands::STDIO)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Output file, or '-' for stdout (the default when stdout is not a terminal)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Algorithm, with optional parameter (e.g. bwt:100000), several joined with '+' (e.g. rle+lz77+huffman),
+    /// or 'auto' to try several and keep the smallest output for each file
+    #[arg(short, long, value_name = "ALGORITHM", default_value = "huffman")]
+    pub algorithm: AlgorithmChoice,
+
+    /// Preset dictionary for LZ77 (must be the same for compress and decompress)
+    #[arg(short = 'D', long, value_name = "FILE")]
+    pub dictionary: Option<PathBuf>,
+
+    /// Decompress the output in memory and compare it with the input before writing it
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Do not record the input file's name, permissions and modification time in the header
+    #[arg(long)]
+    pub no_preserve: bool,
+
+    #[command(flatten)]
+    pub level: LevelArgs,
+
+    #[command(flatten)]
+    pub blocks: BlockArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DecompressArgs {
+    /// Files to decompress, '-' for stdin, or directories and glob patterns for batch mode
+    #[arg(value_name = "INPUT", default_value = commands::STDIO)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Output file, or '-' for stdout (the default when stdout is not a terminal)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Algorithm for files without a header; otherwise it is read from the file
+    #[arg(short, long, value_name = "ALGORITHM")]
+    pub algorithm: Option<AlgorithmSpec>,
+
+    /// Preset dictionary the file was compressed with
+    #[arg(short = 'D', long, value_name = "FILE")]
+    pub dictionary: Option<PathBuf>,
+
+    /// Threads for files compressed in blocks (0 = all cores)
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    pub threads: usize,
+
+    /// Decompress only this byte range of a seekable file
+    #[arg(short, long, value_name = "START[:LEN]")]
+    pub range: Option<String>,
+
+    /// Do not restore the recorded file name, permissions and modification time
+    #[arg(long)]
+    pub no_preserve: bool,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct BenchArgs {
+    /// Files or directories (searched recursively) to benchmark on, or '-' for stdin
+    #[arg(value_name = "INPUT", required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Algorithms to compare (repeatable; default: every codec)
+    #[arg(short, long, value_name = "ALGORITHM")]
+    pub algorithm: Vec<AlgorithmSpec>,
+
+    /// Also run each codec's parameter variants (e.g. lzw:12, ppm:6, arith:static)
+    #[arg(short, long)]
+    pub levels: bool,
+
+    /// Time each codec N times per file and keep the fastest run
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: u32,
+
+    /// Report format
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct InfoArgs {
+    /// Compressed file to describe
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct InspectArgs {
+    /// Compressed file to inspect, or '-' for stdin
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Algorithm for files without a header
+    #[arg(short, long, value_name = "ALGORITHM")]
+    pub algorithm: Option<AlgorithmSpec>,
+
+    /// Preset dictionary the file was compressed with
+    #[arg(short = 'D', long, value_name = "FILE")]
+    pub dictionary: Option<PathBuf>,
+
+    /// Block to inspect in files written with --threads or --seekable
+    #[arg(short, long, value_name = "N", default_value_t = 0)]
+    pub block: usize,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct AnalyzeArgs {
+    /// File to analyze, or '-' for stdin
+    #[arg(value_name = "INPUT", default_value = commands::STDIO)]
+    pub input: PathBuf,
+
+    /// Number of most common bytes to list (0 = all)
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 16)]
+    pub top: usize,
+
+    /// Print the report as JSON, with the full histogram
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TestArgs {
+    /// Compressed files or archives to check
+    #[arg(value_name = "INPUT", required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Algorithm for files without a header
+    #[arg(short, long, value_name = "ALGORITHM")]
+    pub algorithm: Option<AlgorithmSpec>,
+
+    /// Preset dictionary the files were compressed with
+    #[arg(short = 'D', long, value_name = "FILE")]
+    pub dictionary: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TrainArgs {
+    /// Sample files representative of the data to compress
+    #[arg(value_name = "SAMPLE", required = true)]
+    pub samples: Vec<PathBuf>,
+
+    /// Dictionary file to write
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// Maximum dictionary size in bytes (at most 32768, the largest LZ77 window)
+    #[arg(short, long, value_name = "BYTES", default_value_t = 4096)]
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PackArgs {
+    /// Files or directories to add (directories are added recursively)
+    #[arg(value_name = "PATH", required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Archive file to write
+    #[arg(short, long, value_name = "ARCHIVE")]
+    pub output: PathBuf,
+
+    /// Algorithm or pipeline for every entry, or 'auto' to pick one per entry; entries that do not shrink are stored
+    #[arg(short, long, value_name = "ALGORITHM", default_value = commands::DEFAULT_ARCHIVE_ALGORITHM)]
+    pub algorithm: AlgorithmChoice,
+
+    #[command(flatten)]
+    pub level: LevelArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct UnpackArgs {
+    /// Archive file to read
+    #[arg(value_name = "ARCHIVE")]
+    pub archive: PathBuf,
+
+    /// Directory to extract into
+    #[arg(short = 'C', long, value_name = "DIR", default_value = ".")]
+    pub directory: PathBuf,
+
+    /// Overwrite existing files
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Also restore setuid, setgid and sticky bits
+    #[arg(long)]
+    pub special_bits: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ListArgs {
+    /// Archive file to read
+    #[arg(value_name = "ARCHIVE")]
+    pub archive: PathBuf,
+}
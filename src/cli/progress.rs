@@ -0,0 +1,83 @@
+// Progress bar drawn on stderr while a single large file is compressed or
+// decompressed, fed by the library's `Progress` callback.
+//
+// The bar only appears once an operation has run for a moment, is redrawn at
+// most a few times a second and is erased when the codec reports completion,
+// so quick runs and the codecs' own log lines are left untouched.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::compression::Progress;
+
+/// Width of the bar itself, in characters
+const BAR_WIDTH: usize = 30;
+/// Runs shorter than this never show a bar
+const SHOW_AFTER: Duration = Duration::from_millis(500);
+/// Minimum time between redraws
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+struct State {
+    last_draw: Option<Instant>,
+}
+
+struct ProgressBar {
+    label: String,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+impl ProgressBar {
+    fn update(&self, processed: u64, total: u64) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now - self.start;
+
+        if processed >= total {
+            // Erase the bar before the codec prints its summary
+            if state.last_draw.take().is_some() {
+                eprint!("\r\x1b[2K");
+                let _ = io::stderr().flush();
+            }
+            return;
+        }
+        if elapsed < SHOW_AFTER || state.last_draw.is_some_and(|last| now - last < REDRAW_INTERVAL) {
+            return;
+        }
+        state.last_draw = Some(now);
+
+        let fraction = if total == 0 { 1.0 } else { processed as f64 / total as f64 };
+        let filled = ((fraction * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+        let rate = processed as f64 / elapsed.as_secs_f64();
+        let eta = if rate > 0.0 { format_duration((total - processed) as f64 / rate) } else { "--:--".to_string() };
+
+        eprint!("\r\x1b[2K{} [{}{}] {:5.1}% {:>8.2} MB/s ETA {}",
+                self.label, "#".repeat(filled), "-".repeat(BAR_WIDTH - filled),
+                fraction * 100.0, rate / (1024.0 * 1024.0), eta);
+        let _ = io::stderr().flush();
+    }
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// A progress callback that draws a bar labelled `label` on stderr, or `None`
+/// when stderr is not a terminal
+pub fn progress_bar(label: &str) -> Option<Progress> {
+    if !io::stderr().is_terminal() {
+        return None;
+    }
+    let bar = Arc::new(ProgressBar {
+        label: label.to_string(),
+        start: Instant::now(),
+        state: Mutex::new(State { last_draw: None }),
+    });
+    Some(Progress::new(move |processed, total| bar.update(processed, total)))
+}
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+
+use crate::compression::huffman::HuffmanCoding;
+use crate::compression::lz77::LZ77;
+use crate::compression::CompressionAlgorithm;
+
+/// Shortest run the RLE codec encodes as a run rather than as literals
+const MIN_RLE_RUN: usize = 4;
+
+/// What `analyze` found out about some data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub size: usize,
+    /// Occurrences of each byte value
+    pub histogram: [usize; 256],
+    /// Number of byte values that occur
+    pub distinct: usize,
+    /// Shannon entropy of single bytes, in bits per byte
+    pub order0_entropy: f64,
+    /// Entropy of each byte given the one before it, in bits per byte
+    pub order1_entropy: f64,
+    /// Bytes needed at the order-0 entropy, the limit for any order-0 coder
+    pub entropy_bound: usize,
+    /// Bytes of Huffman-coded data with the optimal code lengths, without the
+    /// code table
+    pub huffman_bound: usize,
+    /// Size of the actual `HuffmanCoding` output, code table included
+    pub huffman_size: usize,
+    pub runs: RunDistribution,
+    pub lz: LzEstimate,
+}
+
+/// Maximal runs of one repeated byte
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunDistribution {
+    /// Runs of two or more bytes
+    pub runs: usize,
+    /// Bytes in runs long enough for RLE to shorten
+    pub rle_bytes: usize,
+    pub longest: usize,
+    /// Number of runs (including single bytes) per length range, keyed by the
+    /// power of two starting it
+    pub lengths: BTreeMap<usize, usize>,
+}
+
+/// How much of the data LZ77 finds repeated from earlier on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LzEstimate {
+    pub matches: usize,
+    pub literals: usize,
+    /// Bytes covered by matches
+    pub matched_bytes: usize,
+    /// Share of the data covered by matches, from 0 to 1
+    pub redundancy: f64,
+    /// Size of the actual `LZ77` output
+    pub compressed_size: usize,
+}
+
+/// Entropy in bits per symbol of a distribution with `total` samples
+fn entropy(counts: &[usize], total: usize) -> f64 {
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn order1_entropy(data: &[u8]) -> f64 {
+    if data.len() < 2 {
+        return 0.0;
+    }
+    let mut counts = vec![0usize; 256 * 256];
+    for pair in data.windows(2) {
+        counts[pair[0] as usize * 256 + pair[1] as usize] += 1;
+    }
+    // Weight each context's entropy by how often it occurs
+    let pairs = data.len() - 1;
+    counts
+        .chunks_exact(256)
+        .map(|context| {
+            let total: usize = context.iter().sum();
+            if total == 0 { 0.0 } else { total as f64 / pairs as f64 * entropy(context, total) }
+        })
+        .sum()
+}
+
+fn run_distribution(data: &[u8]) -> RunDistribution {
+    let mut distribution = RunDistribution::default();
+    for run in data.chunk_by(|a, b| a == b) {
+        let length = run.len();
+        if length >= 2 {
+            distribution.runs += 1;
+        }
+        if length >= MIN_RLE_RUN {
+            distribution.rle_bytes += length;
+        }
+        distribution.longest = distribution.longest.max(length);
+        *distribution.lengths.entry(1 << length.ilog2()).or_default() += 1;
+    }
+    distribution
+}
+
+fn lz_estimate(data: &[u8]) -> Result<LzEstimate, Box<dyn std::error::Error>> {
+    let compressed = LZ77::new().compress(data)?;
+    let stats = LZ77::token_stats(&compressed)?;
+    Ok(LzEstimate {
+        matches: stats.matches,
+        literals: stats.literals,
+        matched_bytes: stats.matched_bytes,
+        redundancy: if data.is_empty() { 0.0 } else { stats.matched_bytes as f64 / data.len() as f64 },
+        compressed_size: compressed.len(),
+    })
+}
+
+/// Measure `data`: byte statistics and entropies, how close Huffman coding
+/// gets to the entropy, runs of repeated bytes and how much LZ77 finds
+/// repeated. Huffman and LZ77 are run on the whole input at the default level.
+pub fn analyze(data: &[u8]) -> Result<Analysis, Box<dyn std::error::Error>> {
+    let mut histogram = [0usize; 256];
+    for &byte in data {
+        histogram[byte as usize] += 1;
+    }
+    let order0_entropy = if data.is_empty() { 0.0 } else { entropy(&histogram, data.len()) };
+
+    let lengths = HuffmanCoding::code_lengths(data);
+    let huffman_bits: usize = (0..256).map(|byte| histogram[byte] * lengths[byte] as usize).sum();
+
+    Ok(Analysis {
+        size: data.len(),
+        histogram,
+        distinct: histogram.iter().filter(|&&count| count > 0).count(),
+        order0_entropy,
+        order1_entropy: order1_entropy(data),
+        entropy_bound: (order0_entropy * data.len() as f64 / 8.0).ceil() as usize,
+        huffman_bound: huffman_bits.div_ceil(8),
+        huffman_size: HuffmanCoding::default().compress(data)?.len(),
+        runs: run_distribution(data),
+        lz: lz_estimate(data)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn constant_data_has_no_entropy() {
+        let analysis = analyze(&[b'z'; 1000]).unwrap();
+        assert_eq!(analysis.distinct, 1);
+        assert!(close(analysis.order0_entropy, 0.0));
+        assert!(close(analysis.order1_entropy, 0.0));
+        assert_eq!(analysis.entropy_bound, 0);
+        assert_eq!(analysis.runs.longest, 1000);
+    }
+
+    #[test]
+    fn every_byte_once_is_eight_bits() {
+        let data: Vec<u8> = (0..=255).cycle().take(1024).collect();
+        let analysis = analyze(&data).unwrap();
+        assert_eq!(analysis.distinct, 256);
+        assert!(close(analysis.order0_entropy, 8.0));
+        // Each byte always follows the same one
+        assert!(close(analysis.order1_entropy, 0.0));
+        assert_eq!(analysis.entropy_bound, data.len());
+        assert_eq!(analysis.huffman_bound, data.len());
+    }
+
+    #[test]
+    fn huffman_is_bounded_by_entropy() {
+        let skewed: Vec<u8> = (0..3000u32).map(|i| b"aaaaaaabbbbccd"[(i * 7 % 14) as usize]).collect();
+        let text = b"the quick brown fox jumps over the lazy dog".repeat(20);
+        for data in [&skewed, &text] {
+            let analysis = analyze(data).unwrap();
+            assert!(analysis.huffman_bound >= analysis.entropy_bound);
+            // Huffman codes lose less than a bit per byte against the entropy
+            assert!(analysis.huffman_bound < analysis.entropy_bound + data.len() / 8 + 1);
+        }
+        let alternating = analyze(b"abababab").unwrap();
+        assert!(close(alternating.order0_entropy, 1.0));
+        assert!(close(alternating.order1_entropy, 0.0));
+    }
+
+    #[test]
+    fn runs_are_bucketed_by_length() {
+        let runs = run_distribution(b"abbcccddddeeeeeeeee");
+        assert_eq!(runs.runs, 4);
+        assert_eq!(runs.rle_bytes, 4 + 9);
+        assert_eq!(runs.longest, 9);
+        assert_eq!(runs.lengths, BTreeMap::from([(1, 1), (2, 2), (4, 1), (8, 1)]));
+    }
+
+    #[test]
+    fn empty_input() {
+        let analysis = analyze(b"").unwrap();
+        assert_eq!((analysis.size, analysis.distinct, analysis.entropy_bound, analysis.huffman_bound), (0, 0, 0, 0));
+        assert_eq!(analysis.runs, RunDistribution::default());
+    }
+}
@@ -0,0 +1,413 @@
+use crate::compression::bitio::{BitReader, BitWriter};
+use crate::compression::CompressionAlgorithm;
+
+// Frequencies are normalized to sum to 1 << SCALE_BITS
+const SCALE_BITS: u32 = 12;
+const SCALE: u32 = 1 << SCALE_BITS;
+
+// rANS state lives in [RANS_LOW, RANS_LOW << 8) and is renormalized a byte at a time
+const RANS_LOW: u32 = 1 << 23;
+
+pub const DEFAULT_RANS_STATES: usize = 4;
+pub const MAX_RANS_STATES: usize = 32;
+
+/// Symbol frequencies scaled so they sum to exactly `1 << SCALE_BITS`, with
+/// every symbol that occurs keeping a frequency of at least one
+#[derive(Debug, Clone)]
+pub struct NormalizedFrequencies {
+    frequencies: [u32; 256],
+    starts: [u32; 256],
+}
+
+impl NormalizedFrequencies {
+    pub fn from_data(data: &[u8]) -> Self {
+        let mut counts = [0u64; 256];
+        for &byte in data {
+            counts[byte as usize] += 1;
+        }
+        let total: u64 = counts.iter().sum();
+
+        let mut frequencies = [0u32; 256];
+        for (frequency, &count) in frequencies.iter_mut().zip(counts.iter()) {
+            if count > 0 {
+                *frequency = ((count * SCALE as u64 / total) as u32).max(1);
+            }
+        }
+
+        // Rounding leaves the sum a little off; settle the difference on the
+        // most frequent symbols, where it costs the least
+        let mut sum: i64 = frequencies.iter().map(|&f| f as i64).sum();
+        while sum != SCALE as i64 {
+            let largest = (0..256)
+                .filter(|&s| sum < SCALE as i64 || frequencies[s] > 1)
+                .max_by_key(|&s| frequencies[s])
+                .expect("at least one symbol can absorb the difference");
+            if sum < SCALE as i64 {
+                let add = (SCALE as i64 - sum) as u32;
+                frequencies[largest] += add;
+                sum += add as i64;
+            } else {
+                let remove = ((sum - SCALE as i64) as u32).min(frequencies[largest] - 1);
+                frequencies[largest] -= remove;
+                sum -= remove as i64;
+            }
+        }
+
+        Self::from_frequencies(frequencies)
+    }
+
+    fn from_frequencies(frequencies: [u32; 256]) -> Self {
+        let mut starts = [0u32; 256];
+        let mut total = 0;
+        for (start, &frequency) in starts.iter_mut().zip(frequencies.iter()) {
+            *start = total;
+            total += frequency;
+        }
+        NormalizedFrequencies { frequencies, starts }
+    }
+
+    pub fn frequency(&self, symbol: u8) -> u32 {
+        self.frequencies[symbol as usize]
+    }
+
+    /// Serialize as: count u16, then (symbol u8, frequency u16) per present symbol
+    pub fn write(&self, output: &mut Vec<u8>) {
+        let present: Vec<u8> = (0..=255u8).filter(|&s| self.frequency(s) > 0).collect();
+        output.extend_from_slice(&(present.len() as u16).to_le_bytes());
+        for symbol in present {
+            output.push(symbol);
+            output.extend_from_slice(&(self.frequency(symbol) as u16).to_le_bytes());
+        }
+    }
+
+    pub fn read(data: &[u8], pos: &mut usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let count_bytes = data.get(*pos..*pos + 2).ok_or("Truncated frequency table")?;
+        let count = u16::from_le_bytes([count_bytes[0], count_bytes[1]]) as usize;
+        *pos += 2;
+
+        let mut frequencies = [0u32; 256];
+        for _ in 0..count {
+            let entry = data.get(*pos..*pos + 3).ok_or("Truncated frequency table")?;
+            frequencies[entry[0] as usize] = u16::from_le_bytes([entry[1], entry[2]]) as u32;
+            *pos += 3;
+        }
+
+        if frequencies.iter().sum::<u32>() != SCALE {
+            return Err("Frequency table is not normalized".into());
+        }
+        Ok(Self::from_frequencies(frequencies))
+    }
+
+    /// Map every slot in `0..SCALE` to the symbol owning it
+    fn slot_table(&self) -> Vec<u8> {
+        let mut slots = Vec::with_capacity(SCALE as usize);
+        for symbol in 0..=255u8 {
+            slots.extend(std::iter::repeat_n(symbol, self.frequency(symbol) as usize));
+        }
+        slots
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, Box<dyn std::error::Error>> {
+    let bytes = data.get(pos..pos + 4).ok_or("Unexpected end of input")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Range variant of asymmetric numeral systems with interleaved states.
+///
+/// Consecutive symbols are coded by different states in round-robin order, so
+/// the decoder's dependency chains are independent and can overlap in the CPU.
+///
+/// Stream format:
+///   original size  u32 (LE)
+///   states         u8
+///   frequencies    count u16, then (symbol u8, frequency u16) pairs
+///   final states   u32 (LE) each
+///   renormalization bytes
+pub struct Rans {
+    states: usize,
+}
+
+impl Rans {
+    pub fn new(states: usize) -> Self {
+        Rans { states }
+    }
+}
+
+impl Default for Rans {
+    fn default() -> Self {
+        Self::new(DEFAULT_RANS_STATES)
+    }
+}
+
+impl CompressionAlgorithm for Rans {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.states == 0 || self.states > MAX_RANS_STATES {
+            return Err(format!("rANS supports 1 to {} states, got {}", MAX_RANS_STATES, self.states).into());
+        }
+        if data.len() > u32::MAX as usize {
+            return Err("Input too large for rANS".into());
+        }
+
+        eprintln!("Starting rANS encoding ({} states) on {} bytes of data", self.states, data.len());
+
+        let table = NormalizedFrequencies::from_data(data);
+
+        // Encode back to front, collecting bytes in reverse; the decoder then reads forwards
+        let mut states = vec![RANS_LOW; self.states];
+        let mut reversed = Vec::with_capacity(data.len());
+        for (index, &byte) in data.iter().enumerate().rev() {
+            let state = &mut states[index % self.states];
+            let frequency = table.frequency(byte);
+            let start = table.starts[byte as usize];
+
+            let limit = ((RANS_LOW >> SCALE_BITS) as u64) << 8;
+            while *state as u64 >= limit * frequency as u64 {
+                reversed.push(*state as u8);
+                *state >>= 8;
+            }
+            *state = ((*state / frequency) << SCALE_BITS) + (*state % frequency) + start;
+        }
+        for &state in states.iter().rev() {
+            reversed.extend(state.to_be_bytes());
+        }
+        reversed.reverse();
+
+        let mut output = Vec::with_capacity(reversed.len() + 1024);
+        output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        output.push(self.states as u8);
+        table.write(&mut output);
+        output.extend_from_slice(&reversed);
+
+        eprintln!("rANS encoding completed! {} bytes -> {} bytes", data.len(), output.len());
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let original_size = read_u32(data, 0)? as usize;
+        let state_count = *data.get(4).ok_or("Invalid rANS data: too short")? as usize;
+        if state_count == 0 || state_count > MAX_RANS_STATES {
+            return Err(format!("Invalid rANS state count {}", state_count).into());
+        }
+        let mut pos = 5;
+        let table = NormalizedFrequencies::read(data, &mut pos)?;
+        let slots = table.slot_table();
+
+        eprintln!("Starting rANS decoding ({} states) on {} bytes of data", state_count, data.len());
+
+        let mut states = Vec::with_capacity(state_count);
+        for _ in 0..state_count {
+            states.push(read_u32(data, pos)?);
+            pos += 4;
+        }
+
+        let mut output = Vec::new();
+        for index in 0..original_size {
+            let state = &mut states[index % state_count];
+            let slot = *state & (SCALE - 1);
+            let symbol = slots[slot as usize];
+            let frequency = table.frequency(symbol);
+            let start = table.starts[symbol as usize];
+
+            *state = frequency * (*state >> SCALE_BITS) + slot - start;
+            while *state < RANS_LOW {
+                let byte = *data.get(pos).ok_or("Truncated rANS data")?;
+                *state = (*state << 8) | byte as u32;
+                pos += 1;
+            }
+            output.push(symbol);
+        }
+
+        eprintln!("rANS decoding completed! {} bytes restored", output.len());
+
+        Ok(output)
+    }
+}
+
+/// Tabled asymmetric numeral systems (the FSE construction).
+///
+/// Symbols are spread over a table of `1 << 12` states in proportion to their
+/// normalized frequency; each coding step is a table lookup plus a few raw bits.
+///
+/// Stream format:
+///   original size  u32 (LE)
+///   table log      u8
+///   frequencies    count u16, then (symbol u8, frequency u16) pairs
+///   bitstream      initial decoder state, then the bits of each symbol in order
+pub struct Tans;
+
+struct TansTables {
+    /// Per decoder state: symbol, bits to read and base of the next state
+    decode: Vec<(u8, u32, u32)>,
+    /// Per symbol: encoder states for sub-states `frequency..2 * frequency`
+    encode: Vec<Vec<u32>>,
+}
+
+impl Tans {
+    fn build_tables(table: &NormalizedFrequencies) -> TansTables {
+        // Spread symbols across the table with a step coprime to its size
+        let size = SCALE as usize;
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut spread = vec![0u8; size];
+        let mut position = 0;
+        for symbol in 0..=255u8 {
+            for _ in 0..table.frequency(symbol) {
+                spread[position] = symbol;
+                position = (position + step) & (size - 1);
+            }
+        }
+
+        let mut next_decode: Vec<u32> = (0..=255u8).map(|s| table.frequency(s)).collect();
+        let mut next_encode = next_decode.clone();
+        let mut decode = Vec::with_capacity(size);
+        let mut encode: Vec<Vec<u32>> = (0..=255u8)
+            .map(|s| vec![0; table.frequency(s) as usize])
+            .collect();
+
+        for (state, &symbol) in spread.iter().enumerate() {
+            let s = symbol as usize;
+
+            let sub_state = next_decode[s];
+            next_decode[s] += 1;
+            let bits = SCALE_BITS - (31 - sub_state.leading_zeros());
+            decode.push((symbol, bits, (sub_state << bits) - SCALE));
+
+            let sub_state = next_encode[s];
+            next_encode[s] += 1;
+            encode[s][(sub_state - table.frequency(symbol)) as usize] = state as u32 + SCALE;
+        }
+
+        TansTables { decode, encode }
+    }
+}
+
+impl CompressionAlgorithm for Tans {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if data.len() > u32::MAX as usize {
+            return Err("Input too large for tANS".into());
+        }
+
+        eprintln!("Starting tANS encoding on {} bytes of data", data.len());
+
+        let table = NormalizedFrequencies::from_data(data);
+        let tables = Self::build_tables(&table);
+
+        // Encode back to front, remembering each symbol's output bits so they can
+        // be written in decoding order
+        let mut state = SCALE;
+        let mut chunks = Vec::with_capacity(data.len());
+        for &byte in data.iter().rev() {
+            let frequency = table.frequency(byte);
+            let mut bits = 0;
+            while (state >> bits) >= 2 * frequency {
+                bits += 1;
+            }
+            chunks.push((state & ((1 << bits) - 1), bits));
+            state = tables.encode[byte as usize][((state >> bits) - frequency) as usize];
+        }
+
+        let mut writer = BitWriter::new();
+        writer.write(state - SCALE, SCALE_BITS);
+        for &(value, bits) in chunks.iter().rev() {
+            writer.write(value, bits);
+        }
+
+        let mut output = Vec::new();
+        output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        output.push(SCALE_BITS as u8);
+        table.write(&mut output);
+        output.extend_from_slice(&writer.finish());
+
+        eprintln!("tANS encoding completed! {} bytes -> {} bytes", data.len(), output.len());
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let original_size = read_u32(data, 0)? as usize;
+        let table_log = *data.get(4).ok_or("Invalid tANS data: too short")? as u32;
+        if table_log != SCALE_BITS {
+            return Err(format!("Unsupported tANS table log {}", table_log).into());
+        }
+        let mut pos = 5;
+        let table = NormalizedFrequencies::read(data, &mut pos)?;
+        let tables = Self::build_tables(&table);
+
+        eprintln!("Starting tANS decoding on {} bytes of data", data.len());
+
+        let mut reader = BitReader::new(&data[pos..]);
+        let mut state = reader.read(SCALE_BITS).ok_or("Truncated tANS data")?;
+        let mut output = Vec::new();
+        for _ in 0..original_size {
+            let (symbol, bits, base) = tables.decode[state as usize];
+            output.push(symbol);
+            state = base + reader.read(bits).ok_or("Truncated tANS data")?;
+        }
+
+        eprintln!("tANS decoding completed! {} bytes restored", output.len());
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codecs() -> Vec<Box<dyn CompressionAlgorithm>> {
+        vec![Box::new(Rans::default()), Box::new(Rans::new(1)), Box::new(Rans::new(MAX_RANS_STATES)), Box::new(Tans)]
+    }
+
+    #[test]
+    fn roundtrip() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let skewed: Vec<u8> = (0..5000u32).map(|i| if i % 17 == 0 { (i % 251) as u8 } else { b'e' }).collect();
+        for codec in codecs() {
+            for data in [&b""[..], b"x", b"xxxx", &all_bytes, &skewed] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let data = b"the rain in spain stays mainly in the plain ".repeat(30);
+        for codec in codecs() {
+            let compressed = codec.compress(&data).unwrap();
+            for length in 1..compressed.len() {
+                if let Ok(decoded) = codec.decompress(&compressed[..length]) {
+                    assert_eq!(decoded, data, "length {}", length);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_header_fails() {
+        let mut rans = Rans::default().compress(b"abcabc").unwrap();
+        rans[4] = 0;
+        assert!(Rans::default().decompress(&rans).is_err());
+        rans[4] = MAX_RANS_STATES as u8 + 1;
+        assert!(Rans::default().decompress(&rans).is_err());
+
+        let mut tans = Tans.compress(b"abcabc").unwrap();
+        tans[4] += 1;
+        assert!(Tans.decompress(&tans).is_err());
+    }
+}
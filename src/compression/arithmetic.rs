@@ -0,0 +1,566 @@
+use crate::compression::CompressionAlgorithm;
+
+// 32-bit coder state; the interval [low, high] always spans more than a quarter
+// of the code space after renormalization
+const CODE_BITS: u32 = 32;
+const TOP: u64 = (1 << CODE_BITS) - 1;
+const HALF: u64 = 1 << (CODE_BITS - 1);
+const QUARTER: u64 = 1 << (CODE_BITS - 2);
+
+/// Largest cumulative frequency total a model may use
+pub const MAX_TOTAL: u32 = 1 << 16;
+
+// Binary model probabilities are 12-bit, adapting by 1/32 of the error per bit
+const PROBABILITY_BITS: u32 = 12;
+const PROBABILITY_ONE: u32 = 1 << PROBABILITY_BITS;
+const ADAPT_SHIFT: u32 = 5;
+
+/// Integer arithmetic encoder (Witten–Neal–Cleary style, with pending-bit
+/// handling for underflow). Symbols are coded from their cumulative frequency
+/// range `[low, high)` out of `total`.
+pub struct ArithmeticEncoder {
+    low: u64,
+    high: u64,
+    pending_bits: u64,
+    output: Vec<u8>,
+    current_byte: u8,
+    bit_count: u8,
+}
+
+impl ArithmeticEncoder {
+    pub fn new() -> Self {
+        ArithmeticEncoder {
+            low: 0,
+            high: TOP,
+            pending_bits: 0,
+            output: Vec::new(),
+            current_byte: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Narrow the interval to the symbol's range `[cum_low, cum_high)` out of `total`
+    pub fn encode(&mut self, cum_low: u32, cum_high: u32, total: u32) {
+        debug_assert!(cum_low < cum_high && cum_high <= total && total <= MAX_TOTAL);
+
+        let range = self.high - self.low + 1;
+        self.high = self.low + range * cum_high as u64 / total as u64 - 1;
+        self.low += range * cum_low as u64 / total as u64;
+
+        loop {
+            if self.high < HALF {
+                self.emit_bit_with_pending(false);
+            } else if self.low >= HALF {
+                self.emit_bit_with_pending(true);
+                self.low -= HALF;
+                self.high -= HALF;
+            } else if self.low >= QUARTER && self.high < HALF + QUARTER {
+                self.pending_bits += 1;
+                self.low -= QUARTER;
+                self.high -= QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+        }
+    }
+
+    /// Encode one bit with `probability` (12-bit) that the bit is zero
+    pub fn encode_bit(&mut self, probability: u32, bit: bool) {
+        if bit {
+            self.encode(probability, PROBABILITY_ONE, PROBABILITY_ONE);
+        } else {
+            self.encode(0, probability, PROBABILITY_ONE);
+        }
+    }
+
+    /// Flush the final interval and return the coded bytes
+    pub fn finish(mut self) -> Vec<u8> {
+        // Two more bits are enough to pick a value inside the final interval
+        self.pending_bits += 1;
+        let bit = self.low >= QUARTER;
+        self.emit_bit_with_pending(bit);
+
+        if self.bit_count > 0 {
+            self.current_byte <<= 8 - self.bit_count;
+            self.output.push(self.current_byte);
+        }
+        self.output
+    }
+
+    fn emit_bit_with_pending(&mut self, bit: bool) {
+        self.emit_bit(bit);
+        while self.pending_bits > 0 {
+            self.emit_bit(!bit);
+            self.pending_bits -= 1;
+        }
+    }
+
+    fn emit_bit(&mut self, bit: bool) {
+        self.current_byte = (self.current_byte << 1) | bit as u8;
+        self.bit_count += 1;
+        if self.bit_count == 8 {
+            self.output.push(self.current_byte);
+            self.current_byte = 0;
+            self.bit_count = 0;
+        }
+    }
+}
+
+impl Default for ArithmeticEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decoder matching `ArithmeticEncoder`. Reading past the end of the input
+/// yields zero bits, mirroring the encoder's padding; callers check
+/// `exhausted` so a truncated or corrupt stream cannot decode forever.
+pub struct ArithmeticDecoder<'a> {
+    low: u64,
+    high: u64,
+    value: u64,
+    input: &'a [u8],
+    bit_position: usize,
+}
+
+impl<'a> ArithmeticDecoder<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        let mut decoder = ArithmeticDecoder {
+            low: 0,
+            high: TOP,
+            value: 0,
+            input,
+            bit_position: 0,
+        };
+        for _ in 0..CODE_BITS {
+            decoder.value = (decoder.value << 1) | decoder.next_bit();
+        }
+        decoder
+    }
+
+    /// The cumulative frequency the next symbol falls on, out of `total`
+    pub fn target(&self, total: u32) -> u32 {
+        let range = self.high - self.low + 1;
+        (((self.value - self.low + 1) * total as u64 - 1) / range) as u32
+    }
+
+    /// Remove the symbol with range `[cum_low, cum_high)` from the stream
+    pub fn consume(&mut self, cum_low: u32, cum_high: u32, total: u32) {
+        let range = self.high - self.low + 1;
+        self.high = self.low + range * cum_high as u64 / total as u64 - 1;
+        self.low += range * cum_low as u64 / total as u64;
+
+        loop {
+            if self.high < HALF {
+                // Nothing to subtract
+            } else if self.low >= HALF {
+                self.low -= HALF;
+                self.high -= HALF;
+                self.value -= HALF;
+            } else if self.low >= QUARTER && self.high < HALF + QUARTER {
+                self.low -= QUARTER;
+                self.high -= QUARTER;
+                self.value -= QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+            self.value = (self.value << 1) | self.next_bit();
+        }
+    }
+
+    /// Decode one bit coded with `probability` (12-bit) of being zero
+    pub fn decode_bit(&mut self, probability: u32) -> bool {
+        if self.target(PROBABILITY_ONE) < probability {
+            self.consume(0, probability, PROBABILITY_ONE);
+            false
+        } else {
+            self.consume(probability, PROBABILITY_ONE, PROBABILITY_ONE);
+            true
+        }
+    }
+
+    /// Whether more bits have been read than any valid stream of this length
+    /// needs: every input bit plus the `CODE_BITS` read ahead at the start
+    pub fn exhausted(&self) -> bool {
+        self.bit_position > self.input.len() * 8 + CODE_BITS as usize
+    }
+
+    fn next_bit(&mut self) -> u64 {
+        let byte = self.input.get(self.bit_position / 8).copied().unwrap_or(0);
+        let bit = (byte >> (7 - self.bit_position % 8)) & 1;
+        self.bit_position += 1;
+        bit as u64
+    }
+}
+
+/// Order-0 frequency table over byte values, used by both the static and the
+/// adaptive multi-symbol models
+#[derive(Debug, Clone)]
+pub struct FrequencyModel {
+    frequencies: [u32; 256],
+    total: u32,
+}
+
+impl FrequencyModel {
+    /// Every symbol starts with a count of one so it can always be coded
+    pub fn uniform() -> Self {
+        FrequencyModel { frequencies: [1; 256], total: 256 }
+    }
+
+    /// Counts scaled down so the total fits in `MAX_TOTAL`; symbols that occur
+    /// keep a count of at least one
+    pub fn from_data(data: &[u8]) -> Self {
+        let mut counts = [0u64; 256];
+        for &byte in data {
+            counts[byte as usize] += 1;
+        }
+
+        let sum: u64 = counts.iter().sum();
+        let mut frequencies = [0u32; 256];
+        for (frequency, &count) in frequencies.iter_mut().zip(counts.iter()) {
+            if count > 0 {
+                // Leave room for the minimum count of one on every symbol
+                *frequency = ((count * (MAX_TOTAL as u64 - 256) / sum.max(1)) as u32).max(1);
+            }
+        }
+
+        let total = frequencies.iter().sum();
+        FrequencyModel { frequencies, total }
+    }
+
+    pub fn frequency(&self, symbol: u8) -> u32 {
+        self.frequencies[symbol as usize]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    /// Cumulative range `[low, high)` for `symbol`
+    pub fn range(&self, symbol: u8) -> (u32, u32) {
+        let low: u32 = self.frequencies[..symbol as usize].iter().sum();
+        (low, low + self.frequencies[symbol as usize])
+    }
+
+    /// Find the symbol whose range contains `target`, returning it with its range
+    pub fn lookup(&self, target: u32) -> Option<(u8, u32, u32)> {
+        let mut low = 0;
+        for (symbol, &frequency) in self.frequencies.iter().enumerate() {
+            if target < low + frequency {
+                return Some((symbol as u8, low, low + frequency));
+            }
+            low += frequency;
+        }
+        None
+    }
+
+    /// Count one more occurrence of `symbol`, halving all counts when the total
+    /// would exceed `MAX_TOTAL`
+    pub fn update(&mut self, symbol: u8, increment: u32) {
+        self.frequencies[symbol as usize] += increment;
+        self.total += increment;
+
+        if self.total > MAX_TOTAL {
+            self.total = 0;
+            for frequency in self.frequencies.iter_mut() {
+                if *frequency > 0 {
+                    *frequency = (*frequency / 2).max(1);
+                }
+                self.total += *frequency;
+            }
+        }
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        let present: Vec<(u8, u32)> = (0..=255u8)
+            .map(|symbol| (symbol, self.frequency(symbol)))
+            .filter(|&(_, frequency)| frequency > 0)
+            .collect();
+
+        output.extend_from_slice(&(present.len() as u16).to_le_bytes());
+        for (symbol, frequency) in present {
+            output.push(symbol);
+            output.extend_from_slice(&(frequency as u16).to_le_bytes());
+        }
+    }
+
+    fn read(data: &[u8], pos: &mut usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let count_bytes = data.get(*pos..*pos + 2).ok_or("Truncated frequency table")?;
+        let count = u16::from_le_bytes([count_bytes[0], count_bytes[1]]) as usize;
+        *pos += 2;
+
+        let mut frequencies = [0u32; 256];
+        for _ in 0..count {
+            let entry = data.get(*pos..*pos + 3).ok_or("Truncated frequency table")?;
+            frequencies[entry[0] as usize] = u16::from_le_bytes([entry[1], entry[2]]) as u32;
+            *pos += 3;
+        }
+
+        let total: u32 = frequencies.iter().sum();
+        if total == 0 || total > MAX_TOTAL {
+            return Err("Invalid frequency table".into());
+        }
+        Ok(FrequencyModel { frequencies, total })
+    }
+}
+
+/// Adaptive binary model coding a byte as 8 binary decisions down a bit tree
+/// (255 contexts, one per internal node)
+struct BitTreeModel {
+    probabilities: [u32; 256],
+}
+
+impl BitTreeModel {
+    fn new() -> Self {
+        BitTreeModel { probabilities: [PROBABILITY_ONE / 2; 256] }
+    }
+
+    fn adapt(probability: &mut u32, bit: bool) {
+        if bit {
+            *probability -= *probability >> ADAPT_SHIFT;
+        } else {
+            *probability += (PROBABILITY_ONE - *probability) >> ADAPT_SHIFT;
+        }
+        // Keep both outcomes codable
+        *probability = (*probability).clamp(1 << ADAPT_SHIFT, PROBABILITY_ONE - (1 << ADAPT_SHIFT));
+    }
+
+    fn encode(&mut self, encoder: &mut ArithmeticEncoder, byte: u8) {
+        let mut node = 1usize;
+        for shift in (0..8).rev() {
+            let bit = (byte >> shift) & 1 == 1;
+            encoder.encode_bit(self.probabilities[node], bit);
+            Self::adapt(&mut self.probabilities[node], bit);
+            node = (node << 1) | bit as usize;
+        }
+    }
+
+    fn decode(&mut self, decoder: &mut ArithmeticDecoder) -> u8 {
+        let mut node = 1usize;
+        for _ in 0..8 {
+            let bit = decoder.decode_bit(self.probabilities[node]);
+            Self::adapt(&mut self.probabilities[node], bit);
+            node = (node << 1) | bit as usize;
+        }
+        (node & 0xFF) as u8
+    }
+}
+
+/// Probability model used by `ArithmeticCoding`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticModel {
+    /// Order-0 counts gathered in a first pass and stored in the stream header
+    Static,
+    /// Order-0 counts learned while coding, starting from a uniform table
+    Adaptive,
+    /// Adaptive binary decisions over the bits of each byte
+    Binary,
+}
+
+impl ArithmeticModel {
+    fn id(self) -> u8 {
+        match self {
+            ArithmeticModel::Static => 0,
+            ArithmeticModel::Adaptive => 1,
+            ArithmeticModel::Binary => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, Box<dyn std::error::Error>> {
+        match id {
+            0 => Ok(ArithmeticModel::Static),
+            1 => Ok(ArithmeticModel::Adaptive),
+            2 => Ok(ArithmeticModel::Binary),
+            _ => Err(format!("Unknown arithmetic coding model {}", id).into()),
+        }
+    }
+}
+
+// Count added per occurrence by the adaptive model; larger values adapt faster
+const ADAPTIVE_INCREMENT: u32 = 32;
+
+/// Arithmetic coding with an order-0 model.
+///
+/// Unlike Huffman, a symbol is not rounded to a whole number of bits, so very
+/// skewed inputs (e.g. mostly spaces) approach their entropy.
+///
+/// Stream format:
+///   model          u8 (0 static, 1 adaptive, 2 binary)
+///   original size  u32 (LE)
+///   frequencies    static model only: count u16, then (symbol u8, frequency u16) pairs
+///   coded bits
+pub struct ArithmeticCoding {
+    model: ArithmeticModel,
+}
+
+impl ArithmeticCoding {
+    pub fn new(model: ArithmeticModel) -> Self {
+        ArithmeticCoding { model }
+    }
+}
+
+impl Default for ArithmeticCoding {
+    fn default() -> Self {
+        Self::new(ArithmeticModel::Adaptive)
+    }
+}
+
+impl CompressionAlgorithm for ArithmeticCoding {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if data.len() > u32::MAX as usize {
+            return Err("Input too large for arithmetic coding".into());
+        }
+
+        eprintln!("Starting arithmetic coding ({:?} model) on {} bytes of data", self.model, data.len());
+
+        let mut output = vec![self.model.id()];
+        output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        let mut encoder = ArithmeticEncoder::new();
+
+        match self.model {
+            ArithmeticModel::Static => {
+                let model = FrequencyModel::from_data(data);
+                model.write(&mut output);
+                for &byte in data {
+                    let (low, high) = model.range(byte);
+                    encoder.encode(low, high, model.total());
+                }
+            }
+            ArithmeticModel::Adaptive => {
+                let mut model = FrequencyModel::uniform();
+                for &byte in data {
+                    let (low, high) = model.range(byte);
+                    encoder.encode(low, high, model.total());
+                    model.update(byte, ADAPTIVE_INCREMENT);
+                }
+            }
+            ArithmeticModel::Binary => {
+                let mut model = BitTreeModel::new();
+                for &byte in data {
+                    model.encode(&mut encoder, byte);
+                }
+            }
+        }
+
+        output.extend_from_slice(&encoder.finish());
+
+        let compression_ratio = (output.len() as f64 / data.len() as f64) * 100.0;
+        eprintln!("Arithmetic coding completed! {} bytes -> {} bytes ({:.1}%)",
+                 data.len(), output.len(), compression_ratio);
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if data.len() < 5 {
+            return Err("Invalid arithmetic coded data: too short".into());
+        }
+
+        // The model is read from the stream, not taken from `self`
+        let model = ArithmeticModel::from_id(data[0])?;
+        let original_size = u32::from_le_bytes([data[1], data[2], data[3], data[4]]) as usize;
+        let mut pos = 5;
+
+        eprintln!("Starting arithmetic decoding ({:?} model) on {} bytes of data", model, data.len());
+
+        // The size is unchecked until decoding reaches it, so grow as we go
+        let mut output = Vec::new();
+        let ended_early = "Arithmetic coded data ended early";
+
+        match model {
+            ArithmeticModel::Static => {
+                let table = FrequencyModel::read(data, &mut pos)?;
+                let mut decoder = ArithmeticDecoder::new(&data[pos..]);
+                while output.len() < original_size {
+                    if decoder.exhausted() {
+                        return Err(ended_early.into());
+                    }
+                    let target = decoder.target(table.total());
+                    let (byte, low, high) = table.lookup(target).ok_or("Corrupt arithmetic coded data")?;
+                    decoder.consume(low, high, table.total());
+                    output.push(byte);
+                }
+            }
+            ArithmeticModel::Adaptive => {
+                let mut table = FrequencyModel::uniform();
+                let mut decoder = ArithmeticDecoder::new(&data[pos..]);
+                while output.len() < original_size {
+                    if decoder.exhausted() {
+                        return Err(ended_early.into());
+                    }
+                    let target = decoder.target(table.total());
+                    let (byte, low, high) = table.lookup(target).ok_or("Corrupt arithmetic coded data")?;
+                    decoder.consume(low, high, table.total());
+                    table.update(byte, ADAPTIVE_INCREMENT);
+                    output.push(byte);
+                }
+            }
+            ArithmeticModel::Binary => {
+                let mut tree = BitTreeModel::new();
+                let mut decoder = ArithmeticDecoder::new(&data[pos..]);
+                while output.len() < original_size {
+                    if decoder.exhausted() {
+                        return Err(ended_early.into());
+                    }
+                    output.push(tree.decode(&mut decoder));
+                }
+            }
+        }
+
+        eprintln!("Arithmetic decoding completed! {} bytes restored", output.len());
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODELS: [ArithmeticModel; 3] = [ArithmeticModel::Static, ArithmeticModel::Adaptive, ArithmeticModel::Binary];
+
+    #[test]
+    fn roundtrip_every_model() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let text = b"abracadabra, abracadabra".repeat(50);
+        for model in MODELS {
+            let codec = ArithmeticCoding::new(model);
+            for data in [&b""[..], b"x", &all_bytes, &text] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data, "{:?}", model);
+            }
+        }
+    }
+
+    #[test]
+    fn huge_size_with_no_data_fails() {
+        for id in [1u8, 2] {
+            let data = [id, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00];
+            assert!(ArithmeticCoding::default().decompress(&data).is_err());
+        }
+    }
+
+    #[test]
+    fn truncated_streams_terminate() {
+        let data = b"the quick brown fox jumps over the lazy dog ".repeat(40);
+        for model in MODELS {
+            let codec = ArithmeticCoding::new(model);
+            let compressed = codec.compress(&data).unwrap();
+            for length in 0..compressed.len() {
+                if let Ok(decoded) = codec.decompress(&compressed[..length]) {
+                    assert_ne!(decoded, data);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::compression::parallel::{available_threads, run_parallel};
+use crate::compression::{algorithm_from_spec_with_options, CodecOptions};
+
+/// Name accepted in place of a spec to pick one with `select_algorithm`
+pub const AUTO: &str = "auto";
+
+/// Specs tried by default, cheapest first so they win ties: entropy coders for
+/// data without repeats, dictionary and BWT coders for text, PPM for the best
+/// ratio on text and delta transforms for tables of 16/32-bit numbers
+pub const DEFAULT_CANDIDATES: &[&str] = &[
+    "rans", "huffman", "arith", "lzw", "lz78", "bwt+mtf+zrle+arith", "ppm", "delta:2+rans", "delta:4+rans",
+];
+
+/// Bytes in each sample taken from a large input
+pub const SAMPLE_SIZE: usize = 64 * 1024;
+/// Samples taken from inputs too large to try every candidate on in full; odd,
+/// so that one of them is centred on the middle of the input
+pub const SAMPLE_COUNT: usize = 9;
+
+/// How one candidate did on the samples
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trial {
+    pub algorithm: String,
+    /// Total compressed size of the samples, or why the candidate was rejected
+    pub result: Result<usize, String>,
+}
+
+/// The chosen spec and every candidate's result
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub algorithm: String,
+    pub trials: Vec<Trial>,
+}
+
+/// Evenly spaced `SAMPLE_SIZE` slices covering the start, end and middle of
+/// `data`, or all of it if it is small enough to try in full
+pub fn samples(data: &[u8]) -> Vec<&[u8]> {
+    if data.len() <= SAMPLE_SIZE * SAMPLE_COUNT {
+        return vec![data];
+    }
+    let last_start = data.len() - SAMPLE_SIZE;
+    (0..SAMPLE_COUNT)
+        .map(|i| {
+            let start = last_start * i / (SAMPLE_COUNT - 1);
+            &data[start..start + SAMPLE_SIZE]
+        })
+        .collect()
+}
+
+fn trial(spec: &str, samples: &[&[u8]], options: &CodecOptions) -> Result<usize, String> {
+    let codec = algorithm_from_spec_with_options(spec, options).map_err(|e| e.to_string())?;
+    let mut total = 0;
+    for &sample in samples {
+        let compressed = codec.compress(sample).map_err(|e| format!("compression failed: {}", e))?;
+        // A candidate is only worth choosing if its output can be read back
+        let decompressed = codec.decompress(&compressed).map_err(|e| format!("decompression failed: {}", e))?;
+        if decompressed != sample {
+            return Err("roundtrip mismatch".to_string());
+        }
+        total += compressed.len();
+    }
+    Ok(total)
+}
+
+/// Compress samples of `data` with every candidate spec (on all cores) and
+/// pick the one giving the smallest output. Candidates that fail or do not
+/// roundtrip are skipped; ties go to the earlier candidate.
+pub fn select_algorithm(
+    data: &[u8],
+    candidates: &[&str],
+    options: &CodecOptions,
+) -> Result<Selection, Box<dyn std::error::Error>> {
+    let samples = samples(data);
+    let trials: Vec<Trial> = run_parallel(candidates.len(), available_threads(), |i| Trial {
+        algorithm: candidates[i].to_string(),
+        result: trial(candidates[i], &samples, options),
+    });
+
+    let best = trials
+        .iter()
+        .filter_map(|trial| trial.result.as_ref().ok().map(|&size| (size, &trial.algorithm)))
+        .min_by_key(|&(size, _)| size)
+        .map(|(_, algorithm)| algorithm.clone())
+        .ok_or("No candidate algorithm could compress the input")?;
+
+    Ok(Selection { algorithm: best, trials })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(data: &[u8], sample: &[u8]) -> usize {
+        sample.as_ptr() as usize - data.as_ptr() as usize
+    }
+
+    #[test]
+    fn small_inputs_are_tried_whole() {
+        let data = vec![7u8; SAMPLE_SIZE * SAMPLE_COUNT];
+        for data in [&b""[..], b"x", &data] {
+            assert_eq!(samples(data), vec![data]);
+        }
+    }
+
+    #[test]
+    fn samples_cover_start_middle_and_end() {
+        let data = vec![0u8; SAMPLE_SIZE * SAMPLE_COUNT * 3 + 5];
+        let samples = samples(&data);
+        assert_eq!(samples.len(), SAMPLE_COUNT);
+        assert!(samples.iter().all(|sample| sample.len() == SAMPLE_SIZE));
+        let starts: Vec<usize> = samples.iter().map(|sample| offset(&data, sample)).collect();
+        assert_eq!(starts[0], 0);
+        assert_eq!(starts[SAMPLE_COUNT - 1], data.len() - SAMPLE_SIZE);
+        assert!(starts.windows(2).all(|pair| pair[0] < pair[1]));
+        let middle = data.len() / 2;
+        assert!(starts.iter().any(|&start| (start..start + SAMPLE_SIZE).contains(&middle)));
+    }
+
+    #[test]
+    fn default_candidates_all_work() {
+        let text = b"auto picks whichever candidate comes out smallest. ".repeat(40);
+        let selection = select_algorithm(&text, DEFAULT_CANDIDATES, &CodecOptions::default()).unwrap();
+        assert_eq!(selection.trials.len(), DEFAULT_CANDIDATES.len());
+        let sizes: Vec<usize> = selection.trials.iter().map(|trial| trial.result.clone().unwrap()).collect();
+        let best = selection.trials.iter().find(|trial| trial.algorithm == selection.algorithm).unwrap();
+        assert_eq!(best.result, Ok(*sizes.iter().min().unwrap()));
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_candidate() {
+        // Both specs name the same transform, so the sizes are equal
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 3) as u8).collect();
+        for candidates in [["xor", "xor:1"], ["xor:1", "xor"]] {
+            let selection = select_algorithm(&data, &candidates, &CodecOptions::default()).unwrap();
+            assert_eq!(selection.trials[0].result, selection.trials[1].result);
+            assert_eq!(selection.algorithm, candidates[0]);
+        }
+    }
+
+    #[test]
+    fn failing_candidates_are_skipped() {
+        let data = b"abcabcabc".repeat(100);
+        let candidates = ["zip", "bwt:0", "rle"];
+        let selection = select_algorithm(&data, &candidates, &CodecOptions::default()).unwrap();
+        assert_eq!(selection.algorithm, "rle");
+        assert!(selection.trials[0].result.is_err());
+        assert!(selection.trials[1].result.as_ref().unwrap_err().starts_with("compression failed"));
+        assert!(select_algorithm(&data, &["zip", "bwt:0"], &CodecOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+// Least-significant-bit-first bit packing shared by the codecs that emit
+// variable-width codes (tANS, LZW, LZ78).
+
+/// Packs values into bytes, filling each byte from its lowest bit upwards
+pub struct BitWriter {
+    output: Vec<u8>,
+    buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        BitWriter { output: Vec::new(), buffer: 0, bit_count: 0 }
+    }
+
+    /// Append the low `bits` bits of `value` (at most 32 bits per call)
+    pub fn write(&mut self, value: u32, bits: u32) {
+        debug_assert!(bits <= 32);
+        if bits == 0 {
+            return;
+        }
+        let mask = (1u64 << bits) - 1;
+        self.buffer |= (value as u64 & mask) << self.bit_count;
+        self.bit_count += bits;
+
+        while self.bit_count >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Total number of bits written so far
+    pub fn bit_len(&self) -> u64 {
+        self.output.len() as u64 * 8 + self.bit_count as u64
+    }
+
+    /// Pad the final partial byte with zero bits and return the bytes
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.output.push(self.buffer as u8);
+        }
+        self.output
+    }
+}
+
+impl Default for BitWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads values packed by `BitWriter`
+pub struct BitReader<'a> {
+    input: &'a [u8],
+    bit_position: u64,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        BitReader { input, bit_position: 0 }
+    }
+
+    /// Read `bits` bits (at most 32), or `None` if the input is exhausted
+    pub fn read(&mut self, bits: u32) -> Option<u32> {
+        debug_assert!(bits <= 32);
+        if self.remaining() < bits as u64 {
+            return None;
+        }
+
+        let mut value = 0u64;
+        let mut filled = 0;
+        while filled < bits {
+            let byte = self.input[(self.bit_position / 8) as usize] as u64;
+            let offset = (self.bit_position % 8) as u32;
+            let take = (8 - offset).min(bits - filled);
+            let chunk = (byte >> offset) & ((1 << take) - 1);
+            value |= chunk << filled;
+            filled += take;
+            self.bit_position += take as u64;
+        }
+
+        Some(value as u32)
+    }
+
+    /// Skip ahead by `bits` bits, stopping at the end of the input
+    pub fn skip(&mut self, bits: u64) {
+        self.bit_position = (self.bit_position + bits).min(self.input.len() as u64 * 8);
+    }
+
+    /// Number of bits consumed so far
+    pub fn position(&self) -> u64 {
+        self.bit_position
+    }
+
+    /// Number of unread bits
+    pub fn remaining(&self) -> u64 {
+        self.input.len() as u64 * 8 - self.bit_position
+    }
+}
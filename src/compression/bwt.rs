@@ -0,0 +1,353 @@
+use crate::compression::CompressionAlgorithm;
+
+// Default block size, matching bzip2's largest (-9) block
+pub const DEFAULT_BLOCK_SIZE: usize = 900_000;
+
+const EMPTY: usize = usize::MAX;
+
+/// Burrows–Wheeler transform.
+///
+/// The input is split into blocks, each block is replaced by the last column of
+/// its sorted suffix matrix plus the row at which the (implicit) end-of-block
+/// sentinel sits. The transform does not shrink data on its own, but groups
+/// similar contexts together so that MTF, RLE and Huffman stages compress well.
+///
+/// Stream format:
+///   block_size  u32 (LE)
+///   per block:  length u32, primary index u32, `length` transformed bytes
+pub struct BurrowsWheeler {
+    block_size: usize,
+}
+
+impl BurrowsWheeler {
+    pub fn new(block_size: usize) -> Self {
+        BurrowsWheeler { block_size }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Forward transform of a single block, returning (last column, primary index)
+    pub fn transform_block(block: &[u8]) -> (Vec<u8>, usize) {
+        // Shift bytes up by one so 0 can act as the unique, smallest sentinel
+        let mut text: Vec<usize> = block.iter().map(|&b| b as usize + 1).collect();
+        text.push(0);
+
+        let suffix_array = Self::suffix_array(&text, 257);
+
+        let mut last_column = Vec::with_capacity(block.len());
+        let mut primary = 0;
+        for (row, &suffix) in suffix_array.iter().enumerate() {
+            if suffix == 0 {
+                // This row is preceded by the sentinel, which is not stored
+                primary = row;
+            } else {
+                last_column.push(block[suffix - 1]);
+            }
+        }
+
+        (last_column, primary)
+    }
+
+    /// Inverse transform of a single block
+    pub fn inverse_block(last_column: &[u8], primary: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let length = last_column.len();
+        if primary > length {
+            return Err("Invalid BWT primary index".into());
+        }
+
+        // Symbol in row `row` of the full last column, with the sentinel as 0
+        let symbol = |row: usize| -> usize {
+            match row.cmp(&primary) {
+                std::cmp::Ordering::Less => last_column[row] as usize + 1,
+                std::cmp::Ordering::Equal => 0,
+                std::cmp::Ordering::Greater => last_column[row - 1] as usize + 1,
+            }
+        };
+
+        // Number of symbols smaller than each symbol (the first column's bucket starts)
+        let mut counts = [0usize; 257];
+        for row in 0..=length {
+            counts[symbol(row)] += 1;
+        }
+        let mut starts = [0usize; 257];
+        let mut total = 0;
+        for (start, &count) in starts.iter_mut().zip(counts.iter()) {
+            *start = total;
+            total += count;
+        }
+
+        // LF mapping: the row holding the suffix that starts one position earlier
+        let mut lf = vec![0usize; length + 1];
+        for (row, entry) in lf.iter_mut().enumerate() {
+            let sym = symbol(row);
+            *entry = starts[sym];
+            starts[sym] += 1;
+        }
+
+        // Row 0 is the sentinel suffix; walk backwards through the text from there
+        let mut output = vec![0u8; length];
+        let mut row = 0;
+        for position in (0..length).rev() {
+            let sym = symbol(row);
+            if sym == 0 {
+                return Err("Corrupt BWT block: sentinel reached early".into());
+            }
+            output[position] = (sym - 1) as u8;
+            row = lf[row];
+        }
+
+        Ok(output)
+    }
+
+    /// Build the suffix array of `text` with SA-IS (induced sorting) in linear time.
+    /// `text` must end with a unique 0 sentinel and use symbols below `alphabet_size`.
+    pub fn suffix_array(text: &[usize], alphabet_size: usize) -> Vec<usize> {
+        let n = text.len();
+        if n == 1 {
+            return vec![0];
+        }
+
+        // Classify each suffix as S-type (true) or L-type (false)
+        let mut is_s = vec![false; n];
+        is_s[n - 1] = true;
+        for i in (0..n - 1).rev() {
+            is_s[i] = text[i] < text[i + 1] || (text[i] == text[i + 1] && is_s[i + 1]);
+        }
+        let is_lms = |i: usize| i > 0 && is_s[i] && !is_s[i - 1];
+
+        let mut bucket_sizes = vec![0usize; alphabet_size];
+        for &c in text {
+            bucket_sizes[c] += 1;
+        }
+
+        // Step 1: roughly place LMS suffixes at their bucket tails and induce
+        let mut suffix_array = vec![EMPTY; n];
+        let mut tails = Self::bucket_tails(&bucket_sizes);
+        for i in (1..n).filter(|&i| is_lms(i)) {
+            tails[text[i]] -= 1;
+            suffix_array[tails[text[i]]] = i;
+        }
+        Self::induce(&mut suffix_array, text, &is_s, &bucket_sizes);
+
+        // Step 2: name the LMS substrings in sorted order
+        let sorted_lms: Vec<usize> = suffix_array.iter().copied().filter(|&i| is_lms(i)).collect();
+        let mut names = vec![EMPTY; n];
+        let mut current_name = 0;
+        names[sorted_lms[0]] = 0;
+        for pair in sorted_lms.windows(2) {
+            if !Self::lms_substrings_equal(text, &is_s, pair[0], pair[1]) {
+                current_name += 1;
+            }
+            names[pair[1]] = current_name;
+        }
+
+        let lms_positions: Vec<usize> = (1..n).filter(|&i| is_lms(i)).collect();
+        let reduced: Vec<usize> = lms_positions.iter().map(|&i| names[i]).collect();
+
+        // Step 3: sort the LMS suffixes exactly, recursing if names are not unique
+        let reduced_sa = if current_name + 1 < reduced.len() {
+            Self::suffix_array(&reduced, current_name + 1)
+        } else {
+            let mut direct = vec![0usize; reduced.len()];
+            for (i, &name) in reduced.iter().enumerate() {
+                direct[name] = i;
+            }
+            direct
+        };
+
+        // Step 4: place the sorted LMS suffixes and induce the final order
+        suffix_array.fill(EMPTY);
+        let mut tails = Self::bucket_tails(&bucket_sizes);
+        for &index in reduced_sa.iter().rev() {
+            let position = lms_positions[index];
+            tails[text[position]] -= 1;
+            suffix_array[tails[text[position]]] = position;
+        }
+        Self::induce(&mut suffix_array, text, &is_s, &bucket_sizes);
+
+        suffix_array
+    }
+
+    fn bucket_heads(bucket_sizes: &[usize]) -> Vec<usize> {
+        let mut total = 0;
+        bucket_sizes
+            .iter()
+            .map(|&size| {
+                let head = total;
+                total += size;
+                head
+            })
+            .collect()
+    }
+
+    fn bucket_tails(bucket_sizes: &[usize]) -> Vec<usize> {
+        let mut total = 0;
+        bucket_sizes
+            .iter()
+            .map(|&size| {
+                total += size;
+                total
+            })
+            .collect()
+    }
+
+    /// Induce L-type suffixes left to right, then S-type suffixes right to left
+    fn induce(suffix_array: &mut [usize], text: &[usize], is_s: &[bool], bucket_sizes: &[usize]) {
+        let mut heads = Self::bucket_heads(bucket_sizes);
+        for i in 0..suffix_array.len() {
+            let suffix = suffix_array[i];
+            if suffix != EMPTY && suffix > 0 && !is_s[suffix - 1] {
+                let c = text[suffix - 1];
+                suffix_array[heads[c]] = suffix - 1;
+                heads[c] += 1;
+            }
+        }
+
+        let mut tails = Self::bucket_tails(bucket_sizes);
+        for i in (0..suffix_array.len()).rev() {
+            let suffix = suffix_array[i];
+            if suffix != EMPTY && suffix > 0 && is_s[suffix - 1] {
+                let c = text[suffix - 1];
+                tails[c] -= 1;
+                suffix_array[tails[c]] = suffix - 1;
+            }
+        }
+    }
+
+    /// Compare the LMS substrings starting at `a` and `b` (up to and including the next LMS position)
+    fn lms_substrings_equal(text: &[usize], is_s: &[bool], a: usize, b: usize) -> bool {
+        let last = text.len() - 1;
+        if a == last || b == last {
+            return a == b;
+        }
+
+        let is_lms = |i: usize| i > 0 && is_s[i] && !is_s[i - 1];
+        let mut offset = 0;
+        loop {
+            let (i, j) = (a + offset, b + offset);
+            if text[i] != text[j] || is_s[i] != is_s[j] {
+                return false;
+            }
+            if offset > 0 && (is_lms(i) || is_lms(j)) {
+                return is_lms(i) && is_lms(j);
+            }
+            offset += 1;
+        }
+    }
+}
+
+impl Default for BurrowsWheeler {
+    fn default() -> Self {
+        Self::new(DEFAULT_BLOCK_SIZE)
+    }
+}
+
+impl CompressionAlgorithm for BurrowsWheeler {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.block_size == 0 || self.block_size > u32::MAX as usize {
+            return Err(format!("Invalid BWT block size: {}", self.block_size).into());
+        }
+
+        eprintln!("Starting BWT transform on {} bytes of data", data.len());
+
+        let mut output = Vec::with_capacity(data.len() + 4 + 8 * data.len().div_ceil(self.block_size));
+        output.extend_from_slice(&(self.block_size as u32).to_le_bytes());
+
+        for block in data.chunks(self.block_size) {
+            let (last_column, primary) = Self::transform_block(block);
+            output.extend_from_slice(&(block.len() as u32).to_le_bytes());
+            output.extend_from_slice(&(primary as u32).to_le_bytes());
+            output.extend_from_slice(&last_column);
+        }
+
+        eprintln!("BWT transform completed! {} blocks of up to {} bytes",
+                 data.len().div_ceil(self.block_size), self.block_size);
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if data.len() < 4 {
+            return Err("Invalid BWT data: too short".into());
+        }
+
+        eprintln!("Starting inverse BWT on {} bytes of data", data.len());
+
+        // The block size is informational; each block carries its own length
+        let mut output = Vec::with_capacity(data.len());
+        let mut pos = 4;
+
+        while pos < data.len() {
+            if pos + 8 > data.len() {
+                return Err("Truncated BWT block header".into());
+            }
+            let length = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+            let primary = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+            pos += 8;
+
+            let block = data.get(pos..pos + length).ok_or("Truncated BWT block")?;
+            pos += length;
+
+            output.extend_from_slice(&Self::inverse_block(block, primary)?);
+        }
+
+        eprintln!("Inverse BWT completed! {} bytes restored", output.len());
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_block_sizes() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let text = b"banana bandana cabana ".repeat(40);
+        let same = [b'z'; 500];
+        for block_size in [1, 7, 256, DEFAULT_BLOCK_SIZE] {
+            let codec = BurrowsWheeler::new(block_size);
+            for data in [&b""[..], b"x", &all_bytes, &text, &same] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data, "block size {}", block_size);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_streams_decode_whole_blocks() {
+        let data = b"she sells sea shells ".repeat(30);
+        let codec = BurrowsWheeler::new(100);
+        let compressed = codec.compress(&data).unwrap();
+        for length in 0..compressed.len() {
+            if let Ok(decoded) = codec.decompress(&compressed[..length]) {
+                assert!(data.starts_with(&decoded));
+                assert_eq!(decoded.len() % 100, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn bad_primary_index_fails() {
+        let mut compressed = BurrowsWheeler::default().compress(b"abracadabra").unwrap();
+        compressed[8..12].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(BurrowsWheeler::default().decompress(&compressed).is_err());
+    }
+
+    #[test]
+    fn block_size_comes_from_the_spec() {
+        let data = b"banana bandana cabana ".repeat(40);
+        let from_spec = crate::compression::algorithm_from_spec("bwt:100").unwrap();
+        assert_eq!(from_spec.compress(&data).unwrap(), BurrowsWheeler::new(100).compress(&data).unwrap());
+        assert!(crate::compression::algorithm_from_spec("bwt:big").is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+// Substring lengths considered as dictionary candidates
+const CANDIDATE_LENGTHS: &[usize] = &[6, 8, 12, 16, 24, 32, 48, 64];
+
+// Only the best-scoring candidates are considered for selection
+const MAX_CANDIDATES: usize = 20_000;
+
+/// Bytes read from the start of each sample; the rest is ignored
+pub const MAX_SAMPLE_BYTES: usize = 256 * 1024;
+
+/// Bytes read from all samples together; later samples are ignored once it is
+/// reached. Every position is counted once per candidate length, so the
+/// table of candidates grows to several times the bytes read.
+pub const MAX_TRAINING_BYTES: usize = 1024 * 1024;
+
+/// Build a preset dictionary of at most `size` bytes from sample inputs.
+///
+/// Every substring of a few fixed lengths is scored by how often it occurs
+/// times its length. With several samples only substrings found in at least
+/// two of them are kept, since LZ77 already handles repeats within one input;
+/// a single sample (e.g. a corpus of one message per line) keeps any
+/// substring that occurs more than once.
+/// The best candidates are taken greedily, skipping any already covered by the
+/// dictionary and merging ones that overlap a chosen segment. The highest
+/// scoring segments are placed at the end, where they stay inside the LZ77
+/// window even if the dictionary is larger than it.
+///
+/// Only the first `MAX_SAMPLE_BYTES` of each sample and `MAX_TRAINING_BYTES`
+/// in total are used, which bounds the memory training takes.
+pub fn train_dictionary<S: AsRef<[u8]>>(samples: &[S], size: usize) -> Vec<u8> {
+    if size == 0 || samples.is_empty() {
+        return Vec::new();
+    }
+
+    // For each substring: (occurrences, number of samples containing it, last sample counted)
+    let mut counts: HashMap<&[u8], (u64, u32, usize)> = HashMap::new();
+    let mut remaining = MAX_TRAINING_BYTES;
+    for (index, sample) in samples.iter().enumerate() {
+        let sample = sample.as_ref();
+        let sample = &sample[..sample.len().min(MAX_SAMPLE_BYTES).min(remaining)];
+        remaining -= sample.len();
+        for &length in CANDIDATE_LENGTHS {
+            for window in sample.windows(length) {
+                let entry = counts.entry(window).or_insert((0, 0, usize::MAX));
+                entry.0 += 1;
+                if entry.2 != index {
+                    entry.1 += 1;
+                    entry.2 = index;
+                }
+            }
+        }
+    }
+
+    let shared_only = samples.len() > 1;
+    let mut candidates: Vec<(&[u8], u64)> = counts
+        .into_iter()
+        .filter(|&(_, (occurrences, samples, _))| if shared_only { samples >= 2 } else { occurrences >= 2 })
+        .map(|(substring, (occurrences, _, _))| (substring, occurrences * substring.len() as u64))
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.len().cmp(&a.0.len())).then(a.0.cmp(b.0)));
+    candidates.truncate(MAX_CANDIDATES);
+
+    // Segments in order of selection, i.e. most valuable first
+    let mut segments: Vec<Vec<u8>> = Vec::new();
+    let mut total = 0;
+
+    for (candidate, _) in candidates {
+        if total >= size {
+            break;
+        }
+        if segments.iter().any(|segment| contains(segment, candidate)) {
+            continue;
+        }
+
+        if let Some((index, merged)) = segments
+            .iter()
+            .enumerate()
+            .find_map(|(index, segment)| merge_overlapping(segment, candidate).map(|merged| (index, merged)))
+        {
+            total += merged.len() - segments[index].len();
+            segments[index] = merged;
+        } else {
+            total += candidate.len();
+            segments.push(candidate.to_vec());
+        }
+    }
+
+    // Most valuable segments last, trimmed from the front to fit
+    let mut dictionary: Vec<u8> = segments.into_iter().rev().flatten().collect();
+    if dictionary.len() > size {
+        dictionary.drain(..dictionary.len() - size);
+    }
+    dictionary
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.len() >= needle.len() && haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+/// If `candidate` overlaps the start or end of `segment` by at least half its
+/// length, return the two joined into one segment
+fn merge_overlapping(segment: &[u8], candidate: &[u8]) -> Option<Vec<u8>> {
+    let min_overlap = candidate.len().div_ceil(2);
+    let max_overlap = segment.len().min(candidate.len() - 1);
+
+    for overlap in (min_overlap..=max_overlap).rev() {
+        if segment[segment.len() - overlap..] == candidate[..overlap] {
+            return Some([segment, &candidate[overlap..]].concat());
+        }
+        if candidate[candidate.len() - overlap..] == segment[..overlap] {
+            return Some([&candidate[..candidate.len() - overlap], segment].concat());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_sample_keeps_frequent_strings() {
+        let corpus: Vec<u8> = (0..100)
+            .flat_map(|i| format!("{{\"id\": {}, \"status\": \"delivered\"}}\n", i * 7919 % 1000).into_bytes())
+            .collect();
+        let dictionary = train_dictionary(&[corpus], 64);
+        assert!(dictionary.len() <= 64);
+        assert!(contains(&dictionary, b"\"status\": \"delivered\""));
+    }
+
+    #[test]
+    fn shared_strings_across_samples() {
+        let samples = [&b"GET /api/v1/users HTTP/1.1"[..], b"GET /api/v1/orders HTTP/1.1", b"zzzzzzzzzzzz"];
+        let dictionary = train_dictionary(&samples, 256);
+        assert!(contains(&dictionary, b"GET /api/v1/"));
+        assert!(!contains(&dictionary, b"zzzzzz"));
+    }
+
+    #[test]
+    fn empty_inputs() {
+        assert!(train_dictionary::<&[u8]>(&[], 100).is_empty());
+        assert!(train_dictionary(&[b"abc"], 0).is_empty());
+    }
+
+    #[test]
+    fn only_the_start_of_large_samples_is_read() {
+        // Distinct 8-byte counters fill the part that is read, so the phrase
+        // repeated after it is never seen
+        let mut sample: Vec<u8> = (0..MAX_SAMPLE_BYTES as u64 / 8).flat_map(|i| (i * 0x9E37_79B9).to_be_bytes()).collect();
+        sample.extend(b"a phrase that only repeats late. ".repeat(100));
+        let dictionary = train_dictionary(&[&sample], 256);
+        assert!(!contains(&dictionary, b"only repeats late"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::compression::level::CompressionLevel;
 use crate::compression::CompressionAlgorithm;
 use std::collections::{HashMap, BinaryHeap};
 use std::cmp::Ordering;
@@ -58,9 +59,28 @@ impl Ord for HuffmanNode {
     }
 }
 
-pub struct HuffmanCoding;
+/// Huffman coder. From level 4 up, inputs are also tried split into blocks
+/// with their own code tables, and the cheapest split is kept.
+#[derive(Debug, Clone, Default)]
+pub struct HuffmanCoding {
+    level: CompressionLevel,
+}
 
 impl HuffmanCoding {
+    pub fn new(level: CompressionLevel) -> Self {
+        HuffmanCoding { level }
+    }
+
+    /// Block sizes tried besides a single table for the whole input
+    fn block_sizes(level: CompressionLevel) -> &'static [usize] {
+        match level.get() {
+            1..=3 => &[],
+            4..=6 => &[256 * 1024],
+            7 | 8 => &[256 * 1024, 64 * 1024],
+            _ => &[256 * 1024, 64 * 1024, 16 * 1024],
+        }
+    }
+
     // Build a frequency table for all bytes in the data
     fn build_frequency_table(data: &[u8]) -> HashMap<u8, usize> {
         let mut frequency_table = HashMap::new();
@@ -155,101 +175,368 @@ impl HuffmanCoding {
         }
     }
 
-    // Encode the data using the generated codes
-    fn encode_data(data: &[u8], codes: &HashMap<u8, String>) -> Vec<u8> {
-        // First, build a bit string for all our data
-        let mut bit_string = String::new();
-        
-        for &byte in data {
-            if let Some(code) = codes.get(&byte) {
-                bit_string.push_str(code);
+    // Code length of every byte, taken from the depth of its leaf in the tree
+    fn code_lengths_from_codes(codes: &HashMap<u8, String>) -> [u8; 256] {
+        let mut lengths = [0u8; 256];
+        for (&byte, code) in codes {
+            lengths[byte as usize] = code.len() as u8;
+        }
+        lengths
+    }
+
+    /// Assign canonical codes from code lengths: symbols sorted by (length,
+    /// byte) get consecutive codes, so the decoder can rebuild them from the
+    /// lengths alone. Returns (code, length) per byte value.
+    pub fn canonical_codes(lengths: &[u8; 256]) -> [(u64, u8); 256] {
+        let mut symbols: Vec<u8> = (0..=255u8).filter(|&byte| lengths[byte as usize] > 0).collect();
+        symbols.sort_by_key(|&byte| (lengths[byte as usize], byte));
+
+        let mut codes = [(0u64, 0u8); 256];
+        let mut code = 0u64;
+        let mut previous_length = 0u8;
+        for (i, &byte) in symbols.iter().enumerate() {
+            let length = lengths[byte as usize];
+            if i > 0 {
+                code = (code + 1) << (length - previous_length);
             }
+            previous_length = length;
+            codes[byte as usize] = (code, length);
         }
-        
-        println!("Total bits needed: {}", bit_string.len());
-        println!("Original bytes: {}, Compressed bits: {} ({:.1}% of original)", 
-                 data.len() * 8, bit_string.len(), 
-                 (bit_string.len() as f64 / (data.len() * 8) as f64) * 100.0);
-        
-        // Now pack the bits into bytes
-        Self::pack_bits_to_bytes(&bit_string)
+        codes
     }
 
-    // Helper function to pack a bit string into bytes
-    fn pack_bits_to_bytes(bit_string: &str) -> Vec<u8> {
-        let mut result = Vec::new();
+    // Encode the data using the canonical codes, most significant bit first
+    fn encode_data(data: &[u8], codes: &[(u64, u8); 256]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(data.len() / 2);
         let mut current_byte = 0u8;
         let mut bit_count = 0;
-        
-        for bit_char in bit_string.chars() {
-            // Shift the current byte left and add the new bit
-            current_byte = (current_byte << 1) | if bit_char == '1' { 1 } else { 0 };
-            bit_count += 1;
-            
-            // When we have 8 bits, save the byte and start a new one
-            if bit_count == 8 {
-                result.push(current_byte);
-                current_byte = 0;
-                bit_count = 0;
+
+        for &byte in data {
+            let (code, length) = codes[byte as usize];
+            for shift in (0..length).rev() {
+                current_byte = (current_byte << 1) | ((code >> shift) & 1) as u8;
+                bit_count += 1;
+
+                // When we have 8 bits, save the byte and start a new one
+                if bit_count == 8 {
+                    result.push(current_byte);
+                    current_byte = 0;
+                    bit_count = 0;
+                }
             }
         }
-        
+
         // Handle remaining bits (pad with zeros on the right)
         if bit_count > 0 {
-            current_byte <<= 8 - bit_count; // Shift remaining bits to the left
+            current_byte <<= 8 - bit_count;
             result.push(current_byte);
         }
-        
         result
     }
+
+    /// Huffman code length of every byte value in `data` (0 for bytes that do
+    /// not occur), as written to the stream header
+    pub fn code_lengths(data: &[u8]) -> [u8; 256] {
+        let frequency_table = Self::build_frequency_table(data);
+        match Self::build_huffman_tree(&frequency_table) {
+            Some(root) => Self::code_lengths_from_codes(&Self::generate_codes(&root)),
+            None => [0; 256],
+        }
+    }
+
+    /// Read the header written by `compress`: the original size, the code
+    /// length of every byte value and the offset of the bitstream
+    pub fn read_code_table(data: &[u8]) -> Result<(usize, [u8; 256], usize), Box<dyn std::error::Error>> {
+        if data.len() < 6 {
+            return Err("Huffman data too short for its header".into());
+        }
+        let original_size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let count = u16::from_le_bytes([data[4], data[5]]) as usize;
+        if count == BLOCKED {
+            return Err("Huffman stream is split into blocks; read each of `blocks` instead".into());
+        }
+        let table_end = 6 + count * 2;
+        let table = data.get(6..table_end).ok_or("Huffman code length table is truncated")?;
+
+        let mut lengths = [0u8; 256];
+        // Kraft sum in units of 2^-MAX_CODE_LENGTH; a complete code sums to exactly 1
+        let mut kraft = 0u128;
+        for entry in table.chunks_exact(2) {
+            let (byte, length) = (entry[0], entry[1]);
+            if length == 0 || length > MAX_CODE_LENGTH || lengths[byte as usize] != 0 {
+                return Err(format!("Invalid Huffman code length {} for byte {}", length, byte).into());
+            }
+            lengths[byte as usize] = length;
+            kraft += 1u128 << (MAX_CODE_LENGTH - length);
+        }
+        if kraft > 1u128 << MAX_CODE_LENGTH {
+            return Err("Huffman code lengths do not form a prefix code".into());
+        }
+        if count == 0 && original_size > 0 {
+            return Err("Huffman data has no code table".into());
+        }
+        Ok((original_size, lengths, table_end))
+    }
+
+    /// Size of `data` encoded as a single-table stream
+    fn encoded_size(data: &[u8]) -> usize {
+        let lengths = Self::code_lengths(data);
+        let mut counts = [0usize; 256];
+        for &byte in data {
+            counts[byte as usize] += 1;
+        }
+        let bits: usize = (0..256).map(|byte| counts[byte] * lengths[byte] as usize).sum();
+        let symbols = lengths.iter().filter(|&&length| length > 0).count();
+        6 + symbols * 2 + bits.div_ceil(8)
+    }
+
+    /// Size of `data` split into `block_size` blocks, including the framing
+    fn blocked_size(data: &[u8], block_size: usize) -> usize {
+        10 + data.chunks(block_size).map(|block| 4 + Self::encoded_size(block)).sum::<usize>()
+    }
+
+    /// The single-table streams a stream is made of: the stream itself, or
+    /// each block of a blocked one
+    pub fn blocks(data: &[u8]) -> Result<Vec<&[u8]>, Box<dyn std::error::Error>> {
+        if data.get(4..6) != Some(&(BLOCKED as u16).to_le_bytes()[..]) {
+            return Ok(vec![data]);
+        }
+        let count_bytes = data.get(6..10).ok_or("Huffman block count is truncated")?;
+        let count = u32::from_le_bytes([count_bytes[0], count_bytes[1], count_bytes[2], count_bytes[3]]) as usize;
+        let mut blocks = Vec::new();
+        let mut pos = 10;
+        for _ in 0..count {
+            let length_bytes = data.get(pos..pos + 4).ok_or("Huffman block length is truncated")?;
+            let length = u32::from_le_bytes([length_bytes[0], length_bytes[1], length_bytes[2], length_bytes[3]]) as usize;
+            pos += 4;
+            blocks.push(data.get(pos..pos + length).ok_or("Huffman block is truncated")?);
+            pos += length;
+        }
+        if pos != data.len() {
+            return Err("Trailing data after the last Huffman block".into());
+        }
+        Ok(blocks)
+    }
+
+    // Encode `data` as a single-table stream
+    fn encode_block(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        // Build frequency table
+        let frequency_table = Self::build_frequency_table(data);
+
+        // Build Huffman tree
+        let root = Self::build_huffman_tree(&frequency_table).ok_or("Empty frequency table")?;
+
+        // Only the code lengths come from the tree; the codes themselves are
+        // reassigned canonically
+        let lengths = Self::code_lengths_from_codes(&Self::generate_codes(&root));
+        let codes = Self::canonical_codes(&lengths);
+
+        let mut output = Vec::new();
+        output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        output.extend_from_slice(&(frequency_table.len() as u16).to_le_bytes());
+        for byte in 0..=255u8 {
+            let length = lengths[byte as usize];
+            if length == 0 {
+                continue;
+            }
+            output.push(byte);
+            output.push(length);
+        }
+
+        // Encode the actual data
+        output.extend_from_slice(&Self::encode_data(data, &codes));
+        Ok(output)
+    }
+
+    // Decode a single-table stream
+    fn decode_block(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (original_size, lengths, mut pos) = Self::read_code_table(data)?;
+
+        // Canonical decoding tables: for each length, the first code and where
+        // its symbols start in the (length, byte) sorted symbol list
+        let mut symbols: Vec<u8> = (0..=255u8).filter(|&byte| lengths[byte as usize] > 0).collect();
+        symbols.sort_by_key(|&byte| (lengths[byte as usize], byte));
+        let mut count = [0u64; MAX_CODE_LENGTH as usize + 1];
+        for &byte in &symbols {
+            count[lengths[byte as usize] as usize] += 1;
+        }
+        let mut first_code = [0u64; MAX_CODE_LENGTH as usize + 1];
+        let mut first_index = [0u64; MAX_CODE_LENGTH as usize + 1];
+        let (mut code, mut index) = (0u64, 0u64);
+        for length in 1..=MAX_CODE_LENGTH as usize {
+            code = (code + count[length - 1]) << 1;
+            first_code[length] = code;
+            first_index[length] = index;
+            index += count[length];
+        }
+
+        let mut output = Vec::new();
+        let mut bit = 8;
+        let mut current = 0u8;
+        while output.len() < original_size {
+            let mut code = 0u64;
+            let mut length = 0usize;
+            loop {
+                if bit == 8 {
+                    current = *data.get(pos).ok_or("Huffman bitstream ended early")?;
+                    pos += 1;
+                    bit = 0;
+                }
+                code = (code << 1) | ((current >> (7 - bit)) & 1) as u64;
+                bit += 1;
+                length += 1;
+                if length > MAX_CODE_LENGTH as usize {
+                    return Err("Invalid Huffman code in bitstream".into());
+                }
+                if code.wrapping_sub(first_code[length]) < count[length] {
+                    output.push(symbols[(first_index[length] + code - first_code[length]) as usize]);
+                    break;
+                }
+            }
+        }
+        Ok(output)
+    }
 }
 
+/// Longest code length the decoder accepts. Inputs under 4 GiB produce codes
+/// of at most 46 bits, so this only rejects corrupt tables.
+const MAX_CODE_LENGTH: u8 = 64;
+
+/// Code table count marking a blocked stream; a real table has at most 256 entries
+const BLOCKED: usize = 0xFFFF;
+
+/// Static Huffman coding with a canonical code, so only the code length of
+/// each byte has to be stored.
+///
+/// Stream format:
+///   original size  u32 (LE)
+///   code lengths   count u16, then (symbol u8, length u8) pairs
+///   bitstream      canonical codes, most significant bit first, zero padded
+///
+/// Blocked streams, written when separate tables per block come out smaller:
+///   original size  u32 (LE)
+///   marker         0xFFFF u16 in place of the count
+///   block count    u32
+///   blocks         length u32, then a single-table stream as above
 impl CompressionAlgorithm for HuffmanCoding {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         if data.is_empty() {
             return Ok(Vec::new());
         }
-        
-        // Build frequency table
-        let frequency_table = Self::build_frequency_table(data);
-        println!("Built frequency table with {} unique bytes", frequency_table.len());
-        
-        // Build Huffman tree
-        let tree_root = Self::build_huffman_tree(&frequency_table);
-        
-        match tree_root {
-            Some(root) => {
-                println!("Successfully built Huffman tree with root frequency: {}", root.borrow().frequency);
-                
-                // Generate codes from the tree
-                let codes = Self::generate_codes(&root);
-                println!("Generated {} Huffman codes:", codes.len());
-                
-                // Let's print the codes to see what we got!
-                for (byte, code) in &codes {
-                    // Print the byte as a character if it's printable, otherwise as hex
-                    let byte_display = if *byte >= 32 && *byte <= 126 {
-                        format!("'{}'", *byte as char)
-                    } else {
-                        format!("0x{:02X}", byte)
-                    };
-                    println!("  {} -> {}", byte_display, code);
-                }
-                
-                // Encode the actual data
-                let compressed_data = Self::encode_data(data, &codes);
-                println!("Compression successful! {} bytes -> {} bytes", data.len(), compressed_data.len());
-                
-                return Ok(compressed_data);
-            },
-            None => {
-                return Ok(Vec::new());
+        if data.len() > u32::MAX as usize {
+            return Err("Huffman input too large (over 4 GiB)".into());
+        }
+
+        // Keep whichever split into blocks comes out smallest, preferring
+        // a single table on ties
+        let mut best = (Self::encoded_size(data), data.len());
+        for &block_size in Self::block_sizes(self.level) {
+            if block_size < data.len() {
+                best = best.min((Self::blocked_size(data, block_size), block_size));
             }
         }
+        let block_size = best.1;
+        if block_size >= data.len() {
+            return Self::encode_block(data);
+        }
+
+        let block_count = data.len().div_ceil(block_size);
+        let mut output = Vec::new();
+        output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        output.extend_from_slice(&(BLOCKED as u16).to_le_bytes());
+        output.extend_from_slice(&(block_count as u32).to_le_bytes());
+        for block in data.chunks(block_size) {
+            let encoded = Self::encode_block(block)?;
+            output.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+            output.extend_from_slice(&encoded);
+        }
+        Ok(output)
     }
 
     fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        // TODO: Implement Huffman decompression
-        Ok(data.to_vec())
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut output = Vec::new();
+        let blocks = Self::blocks(data)?;
+        for block in &blocks {
+            output.extend_from_slice(&Self::decode_block(block)?);
+        }
+        let original_size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        if output.len() != original_size {
+            return Err("Huffman blocks do not add up to the original size".into());
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Text followed by bytes with a very different distribution, so higher
+    /// levels split it into blocks
+    fn mixed() -> Vec<u8> {
+        let mut data = b"the quick brown fox jumps over the lazy dog. ".repeat(2000);
+        data.extend((0..90_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8));
+        data
+    }
+
+    #[test]
+    fn roundtrip() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(200);
+        for data in [&b""[..], b"x", b"xxxx", &all_bytes, &text] {
+            let compressed = HuffmanCoding::default().compress(data).unwrap();
+            assert_eq!(HuffmanCoding::default().decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn roundtrip_levels() {
+        let mixed = mixed();
+        for level in [CompressionLevel::FASTEST, CompressionLevel::DEFAULT, CompressionLevel::BEST] {
+            let codec = HuffmanCoding::new(level);
+            for data in [&b""[..], b"x", &mixed] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data, "level {}", level);
+            }
+        }
+    }
+
+    #[test]
+    fn best_level_splits_mixed_data() {
+        let data = mixed();
+        let single = HuffmanCoding::new(CompressionLevel::FASTEST).compress(&data).unwrap();
+        let blocked = HuffmanCoding::new(CompressionLevel::BEST).compress(&data).unwrap();
+        assert_eq!(HuffmanCoding::blocks(&single).unwrap().len(), 1);
+        assert!(HuffmanCoding::blocks(&blocked).unwrap().len() > 1);
+        assert!(blocked.len() < single.len());
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let data = b"abracadabra ".repeat(50);
+        let compressed = HuffmanCoding::default().compress(&data).unwrap();
+        for length in 1..compressed.len() {
+            if let Ok(decoded) = HuffmanCoding::default().decompress(&compressed[..length]) {
+                assert_eq!(decoded, data, "length {}", length);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_blocked_streams_fail() {
+        let blocked = HuffmanCoding::new(CompressionLevel::BEST).compress(&mixed()).unwrap();
+        for length in [5, 9, 12, 1000, blocked.len() - 1] {
+            assert!(HuffmanCoding::default().decompress(&blocked[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn wrong_size_fails() {
+        let mut compressed = HuffmanCoding::default().compress(b"hello world").unwrap();
+        compressed[3] = 0x10;
+        assert!(HuffmanCoding::default().decompress(&compressed).is_err());
     }
 }
@@ -0,0 +1,49 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How hard a codec works to shrink its input, from 1 (fastest) to 9 (best
+/// ratio). Codecs map it to their own settings: LZ77 to its window size,
+/// match search depth and parsing, Huffman to how it splits input into
+/// blocks with their own code tables. The level only affects compression;
+/// streams decode the same way whatever level made them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompressionLevel(u8);
+
+impl CompressionLevel {
+    pub const FASTEST: CompressionLevel = CompressionLevel(1);
+    pub const DEFAULT: CompressionLevel = CompressionLevel(6);
+    pub const BEST: CompressionLevel = CompressionLevel(9);
+
+    pub fn new(level: u8) -> Result<Self, Box<dyn std::error::Error>> {
+        if (Self::FASTEST.0..=Self::BEST.0).contains(&level) {
+            Ok(CompressionLevel(level))
+        } else {
+            Err(format!("Invalid compression level {} (expected 1-9)", level).into())
+        }
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl Default for CompressionLevel {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl FromStr for CompressionLevel {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = s.parse().map_err(|_| format!("Invalid compression level '{}' (expected 1-9)", s))?;
+        Self::new(level)
+    }
+}
+
+impl fmt::Display for CompressionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
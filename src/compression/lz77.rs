@@ -1,10 +1,150 @@
+use std::collections::BTreeMap;
+
+use crate::checksum::adler32;
+use crate::compression::level::CompressionLevel;
+use crate::compression::progress::{Progress, REPORT_INTERVAL};
 use crate::compression::CompressionAlgorithm;
 
 // LZ77 configuration constants
-const WINDOW_SIZE: usize = 4096;    // Size of the sliding window (search buffer)
-const LOOKAHEAD_SIZE: usize = 18;   // Size of the lookahead buffer
+pub const WINDOW_SIZE: usize = 32768;   // Largest window any level searches, and the most of a preset dictionary primed into it
+const MAX_MATCH_LENGTH: usize = 258; // Longest match a single token copies
 const MIN_MATCH_LENGTH: usize = 3;  // Minimum match length to be worth encoding
 
+// Hash chains index every position by its first MIN_MATCH_LENGTH bytes
+const HASH_BITS: u32 = 15;
+const HASH_MASK: usize = (1 << HASH_BITS) - 1;
+const NO_POSITION: usize = usize::MAX;
+
+// Token flag announcing the preset dictionary id (u32) the stream was made with
+const DICTIONARY_FLAG: u8 = 0x02;
+
+/// Match search settings for one compression level
+#[derive(Debug, Clone, Copy)]
+struct Effort {
+    /// How far back a match may start (a power of two, at most
+    /// `WINDOW_SIZE` so distances fit the token's u16)
+    window: usize,
+    /// Most hash chain entries compared per position
+    max_chain: usize,
+    /// Stop searching once a match this long is found
+    nice_length: usize,
+    /// Look one byte ahead before taking a match and emit a literal instead
+    /// if a longer match starts there
+    lazy: bool,
+}
+
+impl Effort {
+    fn for_level(level: CompressionLevel) -> Self {
+        let (window, max_chain, nice_length, lazy) = match level.get() {
+            1 => (4096, 4, 8, false),
+            2 => (4096, 8, 16, false),
+            3 => (8192, 16, 32, false),
+            4 => (8192, 16, 16, true),
+            5 => (16384, 32, 32, true),
+            6 => (16384, 128, 128, true),
+            7 => (32768, 256, 128, true),
+            8 => (32768, 1024, MAX_MATCH_LENGTH, true),
+            _ => (32768, 4096, MAX_MATCH_LENGTH, true),
+        };
+        Effort { window, max_chain, nice_length, lazy }
+    }
+}
+
+/// Hash chain match finder over a buffer: `head` holds the latest position
+/// seen for each hash and `prev` links each position in the window to the
+/// previous one with the same hash
+struct MatchFinder<'a> {
+    buffer: &'a [u8],
+    effort: Effort,
+    head: Vec<usize>,
+    prev: Vec<usize>,
+    /// Positions below this are in the chains
+    inserted: usize,
+}
+
+impl<'a> MatchFinder<'a> {
+    fn new(buffer: &'a [u8], effort: Effort) -> Self {
+        MatchFinder {
+            buffer,
+            effort,
+            head: vec![NO_POSITION; HASH_MASK + 1],
+            prev: vec![NO_POSITION; effort.window],
+            inserted: 0,
+        }
+    }
+
+    fn hash(&self, position: usize) -> usize {
+        let bytes = &self.buffer[position..position + MIN_MATCH_LENGTH];
+        (((bytes[0] as usize) << 10) ^ ((bytes[1] as usize) << 5) ^ bytes[2] as usize) & HASH_MASK
+    }
+
+    fn insert_up_to(&mut self, end: usize) {
+        while self.inserted < end {
+            let position = self.inserted;
+            if position + MIN_MATCH_LENGTH <= self.buffer.len() {
+                let hash = self.hash(position);
+                self.prev[position & (self.effort.window - 1)] = self.head[hash];
+                self.head[hash] = position;
+            }
+            self.inserted += 1;
+        }
+    }
+
+    /// Longest match (distance, length) for the bytes at `position`, or a
+    /// length of 0 if none reaches `MIN_MATCH_LENGTH`. Matches may overlap
+    /// `position`, which the decoder handles by copying byte by byte.
+    fn longest_match(&mut self, position: usize) -> (usize, usize) {
+        self.insert_up_to(position);
+        if position + MIN_MATCH_LENGTH > self.buffer.len() {
+            return (0, 0);
+        }
+
+        let max_length = MAX_MATCH_LENGTH.min(self.buffer.len() - position);
+        let mut best = (0, 0);
+        let mut candidate = self.head[self.hash(position)];
+        let mut chain = self.effort.max_chain;
+
+        while candidate != NO_POSITION && chain > 0 {
+            let distance = position - candidate;
+            if distance > self.effort.window {
+                break;
+            }
+
+            // Only a match longer than the best so far is interesting, so
+            // check the byte that would make it longer first
+            let best_length = best.1;
+            if self.buffer[candidate + best_length] == self.buffer[position + best_length] {
+                let length = self.buffer[candidate..]
+                    .iter()
+                    .zip(&self.buffer[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best = (distance, length);
+                    if length >= self.effort.nice_length || length == max_length {
+                        break;
+                    }
+                }
+            }
+
+            // Older entries may have been overwritten by newer positions; a
+            // link that does not go backwards ends the chain
+            let next = self.prev[candidate & (self.effort.window - 1)];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain -= 1;
+        }
+
+        if best.1 < MIN_MATCH_LENGTH {
+            (0, 0)
+        } else {
+            best
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Match {
     distance: u16,
@@ -12,54 +152,68 @@ struct Match {
     next_char: u8,
 }
 
-pub struct LZ77;
+/// Token counts of an LZ77 stream, from `LZ77::token_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenStats {
+    pub original_size: usize,
+    /// Id of the preset dictionary the stream was made with
+    pub dictionary_id: Option<u32>,
+    pub literals: usize,
+    pub matches: usize,
+    /// Bytes copied by matches, not counting the literal byte after each one
+    pub matched_bytes: usize,
+    /// Number of matches of each length
+    pub lengths: BTreeMap<u16, usize>,
+    /// Number of matches per distance range, keyed by the power of two starting it
+    pub distances: BTreeMap<u16, usize>,
+}
+
+/// A preset dictionary primes the sliding window before the first byte, so even
+/// short inputs can refer back to common strings. The decoder must be given the
+/// same dictionary; its Adler-32 id is written to the stream and checked.
+#[derive(Default)]
+pub struct LZ77 {
+    dictionary: Option<Vec<u8>>,
+    progress: Option<Progress>,
+    level: CompressionLevel,
+}
 
 impl LZ77 {
-    /// Find the longest match in the search buffer for the lookahead buffer
-    fn find_longest_match(data: &[u8], position: usize) -> Match {
-        let mut best_match = Match {
-            distance: 0,
-            length: 0,
-            next_char: if position < data.len() { data[position] } else { 0 },
-        };
+    pub fn new() -> Self {
+        LZ77 { dictionary: None, progress: None, level: CompressionLevel::default() }
+    }
 
-        // Define the search window bounds
-        let search_start = if position >= WINDOW_SIZE { position - WINDOW_SIZE } else { 0 };
-        let search_end = position;
-        
-        // Define the lookahead bounds
-        let lookahead_start = position;
-        let lookahead_end = std::cmp::min(position + LOOKAHEAD_SIZE, data.len());
+    /// Compress at `level`: higher levels search a larger window (4K up to
+    /// 32K), follow longer hash chains and parse lazily from level 4 up
+    pub fn with_level(mut self, level: CompressionLevel) -> Self {
+        self.level = level;
+        self
+    }
 
-        // Search for matches in the search buffer
-        for search_pos in search_start..search_end {
-            let mut match_length = 0;
-            
-            // Calculate how long the match is
-            while search_pos + match_length < search_end &&
-                  lookahead_start + match_length < lookahead_end &&
-                  data[search_pos + match_length] == data[lookahead_start + match_length] {
-                match_length += 1;
-                
-                // Prevent infinite matches by limiting to the distance
-                if match_length >= position - search_pos {
-                    break;
-                }
-            }
+    /// Report compression progress to `progress` every `REPORT_INTERVAL` bytes
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
 
-            // Update best match if this one is longer and meets minimum length
-            if match_length >= MIN_MATCH_LENGTH && match_length > best_match.length as usize {
-                best_match.distance = (position - search_pos) as u16;
-                best_match.length = match_length as u16;
-                best_match.next_char = if lookahead_start + match_length < data.len() {
-                    data[lookahead_start + match_length]
-                } else {
-                    0
-                };
-            }
-        }
+    /// Use `dictionary` as a preset dictionary. Only its last `WINDOW_SIZE`
+    /// bytes are primed, and lower levels search less of them (the last 4 KiB
+    /// at level 1), so the most useful strings belong at the end.
+    pub fn with_dictionary(dictionary: Vec<u8>) -> Self {
+        LZ77 { dictionary: Some(dictionary), ..Self::new() }
+    }
 
-        best_match
+    /// Id recorded in streams compressed with `dictionary`
+    pub fn dictionary_id(dictionary: &[u8]) -> u32 {
+        adler32(dictionary)
+    }
+
+    /// The part of the dictionary that fits in the window
+    fn window_prefix(&self) -> &[u8] {
+        match &self.dictionary {
+            Some(dictionary) => &dictionary[dictionary.len().saturating_sub(WINDOW_SIZE)..],
+            None => &[],
+        }
     }
 
     /// Encode a match or literal into the output buffer
@@ -78,6 +232,37 @@ impl LZ77 {
         Ok(())
     }
 
+    /// Count the literals and matches of a compressed stream without decoding
+    /// it (no dictionary is needed)
+    pub fn token_stats(data: &[u8]) -> Result<TokenStats, Box<dyn std::error::Error>> {
+        let mut stats = TokenStats::default();
+        if data.is_empty() {
+            return Ok(stats);
+        }
+        let header = data.get(..4).ok_or("Invalid compressed data: too short")?;
+        stats.original_size = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let mut pos = 4;
+        if data.get(pos) == Some(&DICTIONARY_FLAG) {
+            let id_bytes = data.get(pos + 1..pos + 5).ok_or("Unexpected end of input for dictionary id")?;
+            stats.dictionary_id = Some(u32::from_le_bytes([id_bytes[0], id_bytes[1], id_bytes[2], id_bytes[3]]));
+            pos += 5;
+        }
+
+        while pos < data.len() {
+            let token = Self::decode_token(data, &mut pos)?;
+            if token.length == 0 {
+                stats.literals += 1;
+            } else {
+                stats.matches += 1;
+                stats.matched_bytes += token.length as usize;
+                *stats.lengths.entry(token.length).or_default() += 1;
+                let bucket = if token.distance == 0 { 0 } else { 1 << (15 - token.distance.leading_zeros()) };
+                *stats.distances.entry(bucket).or_default() += 1;
+            }
+        }
+        Ok(stats)
+    }
+
     /// Decode a token from the input buffer
     fn decode_token(input: &[u8], pos: &mut usize) -> Result<Match, Box<dyn std::error::Error>> {
         if *pos >= input.len() {
@@ -129,53 +314,113 @@ impl CompressionAlgorithm for LZ77 {
             return Ok(Vec::new());
         }
 
-        println!("Starting LZ77 compression on {} bytes of data", data.len());
+        if data.len() > u32::MAX as usize {
+            return Err("LZ77 input too large (over 4 GiB)".into());
+        }
+
+        eprintln!("Starting LZ77 compression on {} bytes of data", data.len());
         
         let mut output = Vec::new();
-        let mut position = 0;
         
         // Add a simple header with original size for verification
         output.extend_from_slice(&(data.len() as u32).to_le_bytes());
-        
-        while position < data.len() {
-            let token = Self::find_longest_match(data, position);
-            
-            Self::encode_token(&mut output, &token)?;
-            
-            // Move position forward
-            if token.length == 0 {
-                position += 1; // Just a literal
-            } else {
-                position += token.length as usize; // Skip the matched portion
-                if position < data.len() {
-                    position += 1; // Include the next character
+
+        // Record which dictionary primes the window, then search over dictionary + data
+        let prefix = self.window_prefix();
+        if let Some(dictionary) = &self.dictionary {
+            output.push(DICTIONARY_FLAG);
+            output.extend_from_slice(&Self::dictionary_id(dictionary).to_le_bytes());
+        }
+        let buffer = [prefix, data].concat();
+        let effort = Effort::for_level(self.level);
+        let mut finder = MatchFinder::new(&buffer, effort);
+        let mut position = prefix.len();
+        let mut next_report = position + REPORT_INTERVAL;
+        // Match found at `position` while looking ahead from the byte before
+        let mut pending = None;
+
+        while position < buffer.len() {
+            if let Some(progress) = &self.progress {
+                if position >= next_report {
+                    progress.report((position - prefix.len()) as u64, data.len() as u64);
+                    next_report = position + REPORT_INTERVAL;
+                }
+            }
+
+            let (distance, length) = pending.take().unwrap_or_else(|| finder.longest_match(position));
+
+            // A longer match one byte on is worth a literal now
+            if length > 0 && effort.lazy && length < effort.nice_length && position + 1 < buffer.len() {
+                let next = finder.longest_match(position + 1);
+                if next.1 > length {
+                    Self::encode_token(&mut output, &Match { distance: 0, length: 0, next_char: buffer[position] })?;
+                    pending = Some(next);
+                    position += 1;
+                    continue;
                 }
             }
+
+            let token = Match {
+                distance: distance as u16,
+                length: length as u16,
+                next_char: buffer.get(position + length).copied().unwrap_or(0),
+            };
+            Self::encode_token(&mut output, &token)?;
+
+            // Skip the matched bytes and the literal after them
+            position = (position + length + 1).min(buffer.len());
+        }
+
+        if let Some(progress) = &self.progress {
+            progress.report(data.len() as u64, data.len() as u64);
         }
 
         let compression_ratio = (output.len() as f64 / data.len() as f64) * 100.0;
-        println!("LZ77 compression completed!");
-        println!("Original size: {} bytes", data.len());
-        println!("Compressed size: {} bytes", output.len());
-        println!("Compression ratio: {:.1}%", compression_ratio);
-        println!("Space saved: {:.1}%", 100.0 - compression_ratio);
+        eprintln!("LZ77 compression completed!");
+        eprintln!("Original size: {} bytes", data.len());
+        eprintln!("Compressed size: {} bytes", output.len());
+        eprintln!("Compression ratio: {:.1}%", compression_ratio);
+        eprintln!("Space saved: {:.1}%", 100.0 - compression_ratio);
 
         Ok(output)
     }
 
     fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
         if data.len() < 4 {
             return Err("Invalid compressed data: too short".into());
         }
 
-        println!("Starting LZ77 decompression on {} bytes of compressed data", data.len());
+        eprintln!("Starting LZ77 decompression on {} bytes of compressed data", data.len());
         
         // Read the original size from the header
         let original_size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
-        let mut output = Vec::with_capacity(original_size);
         let mut pos = 4; // Skip the header
+
+        // A stream made with a preset dictionary names it; refuse to guess
+        let mut prefix: &[u8] = &[];
+        if data.get(pos) == Some(&DICTIONARY_FLAG) {
+            let id_bytes = data.get(pos + 1..pos + 5).ok_or("Unexpected end of input for dictionary id")?;
+            let expected = u32::from_le_bytes([id_bytes[0], id_bytes[1], id_bytes[2], id_bytes[3]]);
+            pos += 5;
+
+            let dictionary = self.dictionary.as_ref().ok_or_else(|| {
+                format!("Stream requires preset dictionary {:08x}, but none was supplied", expected)
+            })?;
+            let actual = Self::dictionary_id(dictionary);
+            if actual != expected {
+                return Err(format!("Preset dictionary mismatch: stream expects {:08x}, got {:08x}", expected, actual).into());
+            }
+            prefix = self.window_prefix();
+        }
+
+        // Decode after the dictionary so matches can reach back into it
+        let target_size = prefix.len() + original_size;
+        let mut output = prefix.to_vec();
         
-        while pos < data.len() && output.len() < original_size {
+        while pos < data.len() && output.len() < target_size {
             let token = Self::decode_token(data, &mut pos)?;
             
             if token.length == 0 {
@@ -200,18 +445,109 @@ impl CompressionAlgorithm for LZ77 {
                 }
                 
                 // Add the next character if we haven't reached the end
-                if output.len() < original_size {
+                if output.len() < target_size {
                     output.push(token.next_char);
                 }
             }
         }
 
-        // Truncate to the original size in case we went over
-        output.truncate(original_size);
+        if output.len() < target_size {
+            return Err("LZ77 data ended early".into());
+        }
+
+        // Drop the dictionary and truncate to the original size in case we went over
+        output.truncate(target_size);
+        output.drain(..prefix.len());
         
-        println!("LZ77 decompression completed!");
-        println!("Decompressed {} bytes to {} bytes", data.len(), output.len());
+        eprintln!("LZ77 decompression completed!");
+        eprintln!("Decompressed {} bytes to {} bytes", data.len(), output.len());
         
         Ok(output)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes that LZ77 finds no repeats in
+    fn noise(length: usize, mut seed: u32) -> Vec<u8> {
+        (0..length)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn roundtrip_levels() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let text: Vec<u8> = (0..4000u32).flat_map(|i| format!("line {} of {}\n", i % 97, i % 13).into_bytes()).collect();
+        let long_run = [b'z'; 2000];
+        for level in 1..=9 {
+            let codec = LZ77::new().with_level(CompressionLevel::new(level).unwrap());
+            for data in [&b""[..], b"x", &all_bytes, &text, &long_run] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data, "level {}", level);
+            }
+        }
+    }
+
+    #[test]
+    fn level_comes_from_the_spec() {
+        let data = b"how much wood would a woodchuck chuck ".repeat(30);
+        let from_spec = crate::compression::algorithm_from_spec("lz77:1").unwrap();
+        let fastest = LZ77::new().with_level(CompressionLevel::FASTEST);
+        assert_eq!(from_spec.compress(&data).unwrap(), fastest.compress(&data).unwrap());
+        for spec in ["lz77:0", "lz77:10", "huffman:x"] {
+            assert!(crate::compression::algorithm_from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let data = b"how much wood would a woodchuck chuck ".repeat(30);
+        let compressed = LZ77::new().compress(&data).unwrap();
+        for length in 1..compressed.len() {
+            if let Ok(decoded) = LZ77::new().decompress(&compressed[..length]) {
+                assert_eq!(decoded, data, "length {}", length);
+            }
+        }
+    }
+
+    #[test]
+    fn dictionary_must_match() {
+        let dictionary = b"{\"user\": \"\", \"status\": \"active\"}".to_vec();
+        let message = b"{\"user\": \"ada\", \"status\": \"active\"}";
+        let compressed = LZ77::with_dictionary(dictionary.clone()).compress(message).unwrap();
+        assert_eq!(LZ77::with_dictionary(dictionary).decompress(&compressed).unwrap(), message);
+        assert!(LZ77::new().decompress(&compressed).is_err());
+        assert!(LZ77::with_dictionary(b"something else".to_vec()).decompress(&compressed).is_err());
+        assert_eq!(LZ77::token_stats(&compressed).unwrap().dictionary_id, Some(LZ77::dictionary_id(
+            b"{\"user\": \"\", \"status\": \"active\"}"
+        )));
+    }
+
+    #[test]
+    fn corrupt_tokens_fail() {
+        // A match reaching 5 bytes back from the first byte, then a bad flag
+        let far_match = [4, 0, 0, 0, 0x01, 5, 0, 3, 0, b'a'];
+        let bad_flag = [1, 0, 0, 0, 0x07, b'a'];
+        for data in [&far_match[..], &bad_flag] {
+            assert!(LZ77::new().decompress(data).is_err());
+        }
+    }
+
+    #[test]
+    fn dictionary_beyond_4k_is_used() {
+        // The message only repeats the start of the dictionary, 20 KiB from its end
+        let dictionary = noise(24 * 1024, 1);
+        let message = dictionary[..2000].to_vec();
+        let level = CompressionLevel::BEST;
+        let with = LZ77::with_dictionary(dictionary).with_level(level);
+        let compressed = with.compress(&message).unwrap();
+        assert!(compressed.len() < 100, "{} bytes", compressed.len());
+        assert_eq!(with.decompress(&compressed).unwrap(), message);
+    }
+}
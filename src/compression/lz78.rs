@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use crate::compression::bitio::{BitReader, BitWriter};
+use crate::compression::CompressionAlgorithm;
+
+pub const DEFAULT_MAX_ENTRIES: u32 = 1 << 16;
+
+/// What to do once the dictionary holds `max_entries` phrases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictionaryPolicy {
+    /// Throw the dictionary away and start learning again
+    Reset,
+    /// Keep using the existing phrases but stop adding new ones
+    Freeze,
+}
+
+impl DictionaryPolicy {
+    fn id(self) -> u8 {
+        match self {
+            DictionaryPolicy::Reset => 0,
+            DictionaryPolicy::Freeze => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, Box<dyn std::error::Error>> {
+        match id {
+            0 => Ok(DictionaryPolicy::Reset),
+            1 => Ok(DictionaryPolicy::Freeze),
+            _ => Err(format!("Unknown LZ78 dictionary policy {}", id).into()),
+        }
+    }
+}
+
+/// LZ78: the input is split into phrases, each being a previously seen phrase
+/// extended by one byte. Every phrase is emitted as (dictionary index, byte) and
+/// then added to the dictionary, which the encoder keeps as a trie.
+///
+/// Unlike LZ77 there is no sliding window: matches can only come from the
+/// dictionary, which grows until it hits `max_entries`.
+///
+/// Stream format:
+///   max entries    u32 (LE)
+///   policy         u8 (0 reset, 1 freeze)
+///   original size  u32 (LE)
+///   tokens         index (just wide enough for the current dictionary) + 8-bit byte, LSB first
+pub struct Lz78 {
+    max_entries: u32,
+    policy: DictionaryPolicy,
+}
+
+impl Lz78 {
+    pub fn new(max_entries: u32, policy: DictionaryPolicy) -> Self {
+        Lz78 { max_entries, policy }
+    }
+
+    /// Bits needed to write any index into a dictionary of `size` phrases
+    fn index_bits(size: usize) -> u32 {
+        usize::BITS - (size.max(1) - 1).leading_zeros()
+    }
+}
+
+impl Default for Lz78 {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ENTRIES, DictionaryPolicy::Reset)
+    }
+}
+
+/// Trie of phrases; node 0 is the empty phrase
+struct PhraseTrie {
+    children: Vec<HashMap<u8, u32>>,
+}
+
+impl PhraseTrie {
+    fn new() -> Self {
+        PhraseTrie { children: vec![HashMap::new()] }
+    }
+
+    fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    fn child(&self, node: u32, byte: u8) -> Option<u32> {
+        self.children[node as usize].get(&byte).copied()
+    }
+
+    fn insert(&mut self, node: u32, byte: u8) {
+        let child = self.children.len() as u32;
+        self.children[node as usize].insert(byte, child);
+        self.children.push(HashMap::new());
+    }
+}
+
+impl CompressionAlgorithm for Lz78 {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.max_entries < 2 {
+            return Err("LZ78 dictionary needs room for at least 2 entries".into());
+        }
+        if data.len() > u32::MAX as usize {
+            return Err("Input too large for LZ78".into());
+        }
+
+        eprintln!("Starting LZ78 compression ({} entries, {:?} when full) on {} bytes of data",
+                 self.max_entries, self.policy, data.len());
+
+        let mut output = Vec::new();
+        output.extend_from_slice(&self.max_entries.to_le_bytes());
+        output.push(self.policy.id());
+        output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+        let mut trie = PhraseTrie::new();
+        let mut writer = BitWriter::new();
+        let mut phrases = 0usize;
+        let mut node = 0u32;
+
+        for &byte in data {
+            if let Some(child) = trie.child(node, byte) {
+                node = child;
+                continue;
+            }
+
+            writer.write(node, Self::index_bits(trie.len()));
+            writer.write(byte as u32, 8);
+            phrases += 1;
+
+            if trie.len() < self.max_entries as usize {
+                trie.insert(node, byte);
+            } else if self.policy == DictionaryPolicy::Reset {
+                trie = PhraseTrie::new();
+            }
+            node = 0;
+        }
+
+        // Input ended inside a known phrase: emit it with a filler byte the
+        // decoder drops when it truncates to the original size
+        if node != 0 {
+            writer.write(node, Self::index_bits(trie.len()));
+            writer.write(0, 8);
+            phrases += 1;
+        }
+
+        output.extend_from_slice(&writer.finish());
+
+        let compression_ratio = (output.len() as f64 / data.len() as f64) * 100.0;
+        eprintln!("LZ78 compression completed! {} phrases, {} bytes -> {} bytes ({:.1}%)",
+                 phrases, data.len(), output.len(), compression_ratio);
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if data.len() < 9 {
+            return Err("Invalid LZ78 data: too short".into());
+        }
+
+        // Dictionary settings come from the stream so any Lz78 can decode it
+        let max_entries = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let policy = DictionaryPolicy::from_id(data[4])?;
+        let original_size = u32::from_le_bytes([data[5], data[6], data[7], data[8]]) as usize;
+        if max_entries < 2 {
+            return Err("Invalid LZ78 dictionary size".into());
+        }
+
+        eprintln!("Starting LZ78 decompression on {} bytes of data", data.len());
+
+        // Each phrase is its parent phrase plus one byte; entry 0 is the empty phrase
+        let mut parents: Vec<u32> = vec![0];
+        let mut bytes: Vec<u8> = vec![0];
+        let mut reader = BitReader::new(&data[9..]);
+        let mut output = Vec::new();
+        let mut phrase = Vec::new();
+
+        while output.len() < original_size {
+            let index = reader.read(Self::index_bits(parents.len())).ok_or("Truncated LZ78 data")?;
+            let byte = reader.read(8).ok_or("Truncated LZ78 data")? as u8;
+            if index as usize >= parents.len() {
+                return Err("Invalid LZ78 dictionary index".into());
+            }
+
+            phrase.clear();
+            let mut current = index;
+            while current != 0 {
+                phrase.push(bytes[current as usize]);
+                current = parents[current as usize];
+            }
+            output.extend(phrase.iter().rev());
+            output.push(byte);
+
+            if parents.len() < max_entries {
+                parents.push(index);
+                bytes.push(byte);
+            } else if policy == DictionaryPolicy::Reset {
+                parents.truncate(1);
+                bytes.truncate(1);
+            }
+        }
+
+        output.truncate(original_size);
+
+        eprintln!("LZ78 decompression completed! {} bytes -> {} bytes", data.len(), output.len());
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codecs() -> [Lz78; 3] {
+        [Lz78::default(), Lz78::new(256, DictionaryPolicy::Reset), Lz78::new(256, DictionaryPolicy::Freeze)]
+    }
+
+    #[test]
+    fn roundtrip_policies() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let text: Vec<u8> = (0..3000u32).flat_map(|i| format!("{} ", i * 13).into_bytes()).collect();
+        for codec in codecs() {
+            for data in [&b""[..], b"x", &all_bytes, &text] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let data = b"a rose is a rose is a rose ".repeat(30);
+        for codec in codecs() {
+            let compressed = codec.compress(&data).unwrap();
+            for length in 1..compressed.len() {
+                assert!(codec.decompress(&compressed[..length]).is_err(), "length {}", length);
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_header_fails() {
+        let compressed = Lz78::default().compress(b"abcabcabc").unwrap();
+        let mut tiny_dictionary = compressed.clone();
+        tiny_dictionary[..4].copy_from_slice(&1u32.to_le_bytes());
+        let mut bad_policy = compressed.clone();
+        bad_policy[4] = 2;
+        for corrupt in [tiny_dictionary, bad_policy] {
+            assert!(Lz78::default().decompress(&corrupt).is_err());
+        }
+    }
+
+    #[test]
+    fn policy_comes_from_the_spec() {
+        let data = b"a rose is a rose is a rose ".repeat(30);
+        let from_spec = crate::compression::algorithm_from_spec("lz78:256:freeze").unwrap();
+        let frozen = Lz78::new(256, DictionaryPolicy::Freeze);
+        assert_eq!(from_spec.compress(&data).unwrap(), frozen.compress(&data).unwrap());
+        assert!(crate::compression::algorithm_from_spec("lz78:256:keep").is_err());
+    }
+}
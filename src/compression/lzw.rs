@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use crate::compression::bitio::{BitReader, BitWriter};
+use crate::compression::CompressionAlgorithm;
+
+/// Magic bytes at the start of every `compress(1)` (.Z) file
+pub const MAGIC: [u8; 2] = [0x1F, 0x9D];
+
+pub const MIN_BITS: u8 = 9;
+pub const MAX_BITS: u8 = 16;
+
+// Flag bits in the third header byte
+pub const BLOCK_MODE_FLAG: u8 = 0x80;
+pub const BITS_MASK: u8 = 0x1F;
+const RESERVED_MASK: u8 = 0x60;
+
+// In block mode code 256 tells the decoder to reset its dictionary
+const CLEAR_CODE: u32 = 256;
+
+// After the dictionary fills, re-check the compression ratio this often (input bytes)
+const CHECK_GAP: usize = 10_000;
+
+/// LZW as implemented by Unix `compress(1)`.
+///
+/// Codes start at 9 bits and grow one bit at a time up to `max_bits` as the
+/// dictionary fills. In block mode the encoder emits a clear code and starts
+/// over when the ratio starts to drop. Output is a complete `.Z` file:
+///
+///   0x1F 0x9D, flags (max bits | 0x80 for block mode), codes packed LSB first
+///
+/// Like the original, codes are written in groups of eight; whenever the code
+/// width changes or the dictionary is cleared, the rest of the current group is
+/// padded so that old decoders stay in step.
+pub struct Lzw {
+    max_bits: u8,
+    block_mode: bool,
+}
+
+impl Lzw {
+    pub fn new(max_bits: u8) -> Self {
+        Lzw { max_bits, block_mode: true }
+    }
+
+    /// Disable clear codes (the old `compress -C` format)
+    pub fn without_block_mode(mut self) -> Self {
+        self.block_mode = false;
+        self
+    }
+
+    fn first_free_code(block_mode: bool) -> u32 {
+        if block_mode { CLEAR_CODE + 1 } else { CLEAR_CODE }
+    }
+
+    /// Largest code before the width has to grow. Like compress(1), the check
+    /// for `max_bits` only happens when widening, so 9-bit streams still step up
+    /// to 10-bit codes once the (then full) dictionary passes 511.
+    fn max_code(bits: u32, max_bits: u32) -> u32 {
+        if bits == max_bits && bits > MIN_BITS as u32 { 1 << max_bits } else { (1 << bits) - 1 }
+    }
+
+    /// Number of padding bits needed to finish the current group of eight codes
+    fn group_padding(bits_since_group_start: u64, bits: u32) -> u64 {
+        let group_bits = bits as u64 * 8;
+        (group_bits - bits_since_group_start % group_bits) % group_bits
+    }
+}
+
+impl Default for Lzw {
+    fn default() -> Self {
+        Self::new(MAX_BITS)
+    }
+}
+
+/// Encoder state mirroring `compress(1)`'s `output()` routine
+struct LzwWriter {
+    writer: BitWriter,
+    group_start: u64,
+    bits: u32,
+    max_bits: u32,
+}
+
+impl LzwWriter {
+    /// Write `code`, then widen (or reset after a clear) if the next code will not fit
+    fn output(&mut self, code: u32, next_free: u32, clear: bool) {
+        self.writer.write(code, self.bits);
+
+        let widen = next_free > Lzw::max_code(self.bits, self.max_bits);
+        if widen || clear {
+            let padding = Lzw::group_padding(self.writer.bit_len() - self.group_start, self.bits);
+            for _ in 0..padding {
+                self.writer.write(0, 1);
+            }
+            self.group_start = self.writer.bit_len();
+            self.bits = if clear { MIN_BITS as u32 } else { self.bits + 1 };
+        }
+    }
+}
+
+impl CompressionAlgorithm for Lzw {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if !(MIN_BITS..=MAX_BITS).contains(&self.max_bits) {
+            return Err(format!("LZW max bits must be {} to {}, got {}", MIN_BITS, MAX_BITS, self.max_bits).into());
+        }
+
+        let mut output = MAGIC.to_vec();
+        output.push(self.max_bits | if self.block_mode { BLOCK_MODE_FLAG } else { 0 });
+
+        // compress(1) writes just the header for an empty file
+        if data.is_empty() {
+            return Ok(output);
+        }
+
+        eprintln!("Starting LZW compression ({}-bit max codes) on {} bytes of data", self.max_bits, data.len());
+
+        let max_bits = self.max_bits as u32;
+        let table_size = 1u32 << max_bits;
+        let first_free = Self::first_free_code(self.block_mode);
+
+        let mut dictionary: HashMap<(u32, u8), u32> = HashMap::new();
+        let mut next_free = first_free;
+        let mut out = LzwWriter { writer: BitWriter::new(), group_start: 0, bits: MIN_BITS as u32, max_bits };
+
+        let mut checkpoint = CHECK_GAP;
+        let mut best_ratio = 0.0;
+
+        let mut current = data[0] as u32;
+        for (index, &byte) in data.iter().enumerate().skip(1) {
+            if let Some(&code) = dictionary.get(&(current, byte)) {
+                current = code;
+                continue;
+            }
+
+            out.output(current, next_free, false);
+
+            if next_free < table_size {
+                dictionary.insert((current, byte), next_free);
+                next_free += 1;
+            } else if self.block_mode && index >= checkpoint {
+                // Dictionary is full: keep it while the ratio improves, otherwise start over
+                checkpoint = index + CHECK_GAP;
+                let ratio = index as f64 / (out.writer.bit_len() as f64 / 8.0 + output.len() as f64);
+                if ratio > best_ratio {
+                    best_ratio = ratio;
+                } else {
+                    best_ratio = 0.0;
+                    dictionary.clear();
+                    next_free = first_free;
+                    out.output(CLEAR_CODE, next_free, true);
+                }
+            }
+
+            current = byte as u32;
+        }
+        out.output(current, next_free, false);
+
+        output.extend_from_slice(&out.writer.finish());
+
+        let compression_ratio = (output.len() as f64 / data.len() as f64) * 100.0;
+        eprintln!("LZW compression completed! {} bytes -> {} bytes ({:.1}%)",
+                 data.len(), output.len(), compression_ratio);
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.len() < 3 || data[..2] != MAGIC {
+            return Err("Not a compress(1) file: missing 0x1F 0x9D header".into());
+        }
+
+        // The header, not `self`, decides the code width and block mode
+        let flags = data[2];
+        let max_bits = (flags & BITS_MASK) as u32;
+        let block_mode = flags & BLOCK_MODE_FLAG != 0;
+        if flags & RESERVED_MASK != 0 {
+            return Err("Unsupported compress(1) flags".into());
+        }
+        if !(MIN_BITS as u32..=MAX_BITS as u32).contains(&max_bits) {
+            return Err(format!("Unsupported LZW code width: {} bits", max_bits).into());
+        }
+
+        eprintln!("Starting LZW decompression ({}-bit max codes) on {} bytes of data", max_bits, data.len());
+
+        let table_size = 1usize << max_bits;
+        let mut prefixes = vec![0u32; table_size];
+        let mut suffixes = vec![0u8; table_size];
+        for (code, suffix) in suffixes.iter_mut().enumerate().take(256) {
+            *suffix = code as u8;
+        }
+
+        let mut reader = BitReader::new(&data[3..]);
+        let mut bits = MIN_BITS as u32;
+        let mut max_code = Self::max_code(bits, max_bits);
+        let mut group_start = 0u64;
+        let mut next_free = Self::first_free_code(block_mode);
+
+        let mut output = Vec::with_capacity(data.len() * 3);
+        let first = match reader.read(bits) {
+            Some(code) => code,
+            None => return Ok(output),
+        };
+        if first >= 256 {
+            return Err("Invalid first LZW code".into());
+        }
+        let mut previous = first;
+        let mut first_byte = first as u8;
+        output.push(first_byte);
+
+        let mut stack = Vec::new();
+        loop {
+            if next_free > max_code {
+                reader.skip(Self::group_padding(reader.position() - group_start, bits));
+                group_start = reader.position();
+                bits += 1;
+                max_code = Self::max_code(bits, max_bits);
+            }
+
+            let code = match reader.read(bits) {
+                Some(code) => code,
+                None => break,
+            };
+
+            if code == CLEAR_CODE && block_mode {
+                reader.skip(Self::group_padding(reader.position() - group_start, bits));
+                group_start = reader.position();
+                bits = MIN_BITS as u32;
+                max_code = Self::max_code(bits, max_bits);
+                // The next code re-fills slot 256 with a dummy entry, as compress(1) does
+                next_free = CLEAR_CODE;
+                continue;
+            }
+
+            // Unwind the string for `code`; a code one past the table is the KwKwK case
+            stack.clear();
+            let mut current = code;
+            if code >= next_free {
+                if code > next_free {
+                    return Err("Invalid LZW code in compressed data".into());
+                }
+                stack.push(first_byte);
+                current = previous;
+            }
+            while current >= 256 {
+                stack.push(suffixes[current as usize]);
+                current = prefixes[current as usize];
+            }
+            first_byte = current as u8;
+            stack.push(first_byte);
+            output.extend(stack.iter().rev());
+
+            if (next_free as usize) < table_size {
+                prefixes[next_free as usize] = previous;
+                suffixes[next_free as usize] = first_byte;
+                next_free += 1;
+            }
+            previous = code;
+        }
+
+        eprintln!("LZW decompression completed! {} bytes -> {} bytes", data.len(), output.len());
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_code_widths() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        // Enough distinct phrases to fill a 9-bit table several times over
+        let text: Vec<u8> = (0..20_000u32).flat_map(|i| format!("{} ", i * 7).into_bytes()).collect();
+        let codecs = [Lzw::default(), Lzw::new(MIN_BITS), Lzw::new(12), Lzw::new(12).without_block_mode()];
+        for codec in &codecs {
+            for data in [&b""[..], b"x", &all_bytes, &text] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_streams_decode_a_prefix() {
+        let data = b"TOBEORNOTTOBEORTOBEORNOT#".repeat(30);
+        let compressed = Lzw::default().compress(&data).unwrap();
+        for length in 0..compressed.len() {
+            if let Ok(decoded) = Lzw::default().decompress(&compressed[..length]) {
+                assert!(data.starts_with(&decoded));
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_header_fails() {
+        let compressed = Lzw::default().compress(b"hello hello").unwrap();
+        for (index, value) in [(0, 0x1E), (2, BLOCK_MODE_FLAG | 8), (2, BLOCK_MODE_FLAG | 17), (2, 0x20 | 16)] {
+            let mut corrupt = compressed.clone();
+            corrupt[index] = value;
+            assert!(Lzw::default().decompress(&corrupt).is_err(), "byte {} = {:#x}", index, value);
+        }
+    }
+}
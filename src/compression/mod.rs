@@ -1,8 +1,207 @@
+pub mod analysis;
+pub mod ans;
+pub mod arithmetic;
+pub mod auto;
+pub mod bitio;
+pub mod bwt;
+pub mod delta;
+pub mod dictionary;
 pub mod huffman;
+pub mod level;
 pub mod lz77;
+pub mod lz78;
+pub mod lzw;
+pub mod mtf;
+pub mod parallel;
+pub mod pipeline;
+pub mod ppm;
+pub mod progress;
 pub mod rle;
+pub mod seekable;
+pub mod zrle;
 
-pub trait CompressionAlgorithm {
+pub use analysis::analyze;
+pub use auto::select_algorithm;
+pub use dictionary::train_dictionary;
+pub use level::CompressionLevel;
+pub use parallel::BlockParallel;
+pub use pipeline::Pipeline;
+pub use progress::Progress;
+pub use seekable::{Seekable, SeekableReader};
+
+pub trait CompressionAlgorithm: Send + Sync {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
     fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
 }
+
+/// Settings supplied alongside a spec that apply to every stage that uses them
+#[derive(Debug, Clone, Default)]
+pub struct CodecOptions {
+    /// Preset dictionary for LZ77 stages
+    pub dictionary: Option<Vec<u8>>,
+    /// Told how far compression has got by stages that report it (LZ77)
+    pub progress: Option<Progress>,
+    /// Effort for stages that have levels (LZ77, Huffman); a level given in
+    /// the spec, e.g. `lz77:9`, takes precedence
+    pub level: CompressionLevel,
+}
+
+/// Names of the single-stage algorithms known to `algorithm_from_name`
+pub const ALGORITHM_NAMES: &[&str] = &[
+    "huffman", "lz77", "rle", "bwt", "mtf", "zrle", "arith", "rans", "tans", "lzw", "lz78", "delta", "xor",
+    "ppm",
+];
+
+/// Look up a single codec by name. Some codecs take a parameter after a colon,
+/// e.g. `bwt:100000` for the block size, `mtf:m1ff` for move-one-from-front or
+/// `lz78:4096:freeze` for the LZ78 dictionary limit and policy. `lz77` and
+/// `huffman` take a compression level, e.g. `lz77:9`.
+///
+/// Delta transforms take `<width>[:<stride>][:zigzag]`, e.g. `delta:4:1:zigzag`
+/// for little-endian 32-bit values or `xor:8` for 64-bit ones.
+pub fn algorithm_from_name(name: &str) -> Result<Box<dyn CompressionAlgorithm>, Box<dyn std::error::Error>> {
+    algorithm_from_name_with_options(name, &CodecOptions::default())
+}
+
+/// Like `algorithm_from_name`, applying `options` to the codec
+pub fn algorithm_from_name_with_options(
+    name: &str,
+    options: &CodecOptions,
+) -> Result<Box<dyn CompressionAlgorithm>, Box<dyn std::error::Error>> {
+    let (base, param) = match name.split_once(':') {
+        Some((base, param)) => (base, Some(param)),
+        None => (name, None),
+    };
+
+    let codec: Box<dyn CompressionAlgorithm> = match (base, param) {
+        ("huffman", None) => Box::new(huffman::HuffmanCoding::new(options.level)),
+        ("huffman", Some(level)) => Box::new(huffman::HuffmanCoding::new(parse_param(name, level)?)),
+        ("lz77", level) => {
+            let level = match level {
+                Some(level) => parse_param(name, level)?,
+                None => options.level,
+            };
+            let mut codec = match &options.dictionary {
+                Some(dictionary) => lz77::LZ77::with_dictionary(dictionary.clone()),
+                None => lz77::LZ77::new(),
+            }
+            .with_level(level);
+            if let Some(progress) = &options.progress {
+                codec = codec.with_progress(progress.clone());
+            }
+            Box::new(codec)
+        }
+        ("rle", None) => Box::new(rle::RunLengthEncoding),
+        ("bwt", None) => Box::new(bwt::BurrowsWheeler::default()),
+        ("bwt", Some(size)) => Box::new(bwt::BurrowsWheeler::new(parse_param(name, size)?)),
+        ("mtf", None) => Box::new(mtf::MoveToFront::default()),
+        ("mtf", Some("m1ff")) => Box::new(mtf::MoveToFront::new(mtf::MtfVariant::MoveOneFromFront)),
+        ("mtf", Some(variant)) => {
+            return Err(format!("Unknown MTF variant '{}' (expected m1ff)", variant).into());
+        }
+        ("zrle", None) => Box::new(zrle::ZeroRunEncoding),
+        ("arith", None) => Box::new(arithmetic::ArithmeticCoding::default()),
+        ("arith", Some(model)) => Box::new(arithmetic::ArithmeticCoding::new(match model {
+            "static" => arithmetic::ArithmeticModel::Static,
+            "adaptive" => arithmetic::ArithmeticModel::Adaptive,
+            "binary" => arithmetic::ArithmeticModel::Binary,
+            _ => return Err(format!("Unknown arithmetic model '{}' (expected static, adaptive or binary)", model).into()),
+        })),
+        ("rans", None) => Box::new(ans::Rans::default()),
+        ("rans", Some(states)) => Box::new(ans::Rans::new(parse_param(name, states)?)),
+        ("tans", None) => Box::new(ans::Tans),
+        ("lzw", None) => Box::new(lzw::Lzw::default()),
+        ("lzw", Some(bits)) => Box::new(lzw::Lzw::new(parse_param(name, bits)?)),
+        ("lz78", None) => Box::new(lz78::Lz78::default()),
+        ("lz78", Some(options)) => {
+            let (entries, policy) = match options.split_once(':') {
+                Some((entries, "reset")) => (entries, lz78::DictionaryPolicy::Reset),
+                Some((entries, "freeze")) => (entries, lz78::DictionaryPolicy::Freeze),
+                Some((_, policy)) => {
+                    return Err(format!("Unknown LZ78 policy '{}' (expected reset or freeze)", policy).into());
+                }
+                None => (options, lz78::DictionaryPolicy::Reset),
+            };
+            Box::new(lz78::Lz78::new(parse_param(name, entries)?, policy))
+        }
+        ("ppm", None) => Box::new(ppm::Ppm::default()),
+        ("ppm", Some(order)) => Box::new(ppm::Ppm::new(parse_param(name, order)?)),
+        ("delta", None) => Box::new(delta::Delta::default()),
+        ("delta", Some(options)) => Box::new(delta_from_options(name, delta::DeltaMode::Subtract, options)?),
+        ("xor", None) => Box::new(delta::Delta::new(delta::DeltaMode::Xor, 1, 1)),
+        ("xor", Some(options)) => Box::new(delta_from_options(name, delta::DeltaMode::Xor, options)?),
+        (_, Some(_)) if ALGORITHM_NAMES.contains(&base) => {
+            return Err(format!("Algorithm '{}' does not take a parameter", base).into());
+        }
+        _ => {
+            return Err(format!("Unknown algorithm: {}. Available algorithms: {}", name, ALGORITHM_NAMES.join(", ")).into());
+        }
+    };
+
+    Ok(codec)
+}
+
+/// Build a codec from a spec: either a single name (`lz77`) or a pipeline of
+/// names joined with `+` (`rle+lz77+huffman`)
+pub fn algorithm_from_spec(spec: &str) -> Result<Box<dyn CompressionAlgorithm>, Box<dyn std::error::Error>> {
+    algorithm_from_spec_with_options(spec, &CodecOptions::default())
+}
+
+/// Like `algorithm_from_spec`, applying `options` to every stage
+pub fn algorithm_from_spec_with_options(
+    spec: &str,
+    options: &CodecOptions,
+) -> Result<Box<dyn CompressionAlgorithm>, Box<dyn std::error::Error>> {
+    if spec.contains(pipeline::STAGE_SEPARATOR) {
+        return Ok(Box::new(Pipeline::from_spec_with_options(spec, options)?));
+    }
+
+    algorithm_from_name_with_options(spec, options)
+}
+
+fn parse_param<T: std::str::FromStr>(name: &str, param: &str) -> Result<T, Box<dyn std::error::Error>> {
+    param
+        .parse()
+        .map_err(|_| format!("Invalid parameter '{}' in '{}'", param, name).into())
+}
+
+fn delta_from_options(name: &str, mode: delta::DeltaMode, options: &str) -> Result<delta::Delta, Box<dyn std::error::Error>> {
+    let mut parts: Vec<&str> = options.split(':').collect();
+    let zigzag = parts.last() == Some(&"zigzag");
+    if zigzag {
+        parts.pop();
+    }
+
+    let (width, stride) = match parts.as_slice() {
+        [width] => (parse_param(name, width)?, 1),
+        [width, stride] => (parse_param(name, width)?, parse_param(name, stride)?),
+        _ => return Err(format!("Invalid options in '{}' (expected <width>[:<stride>][:zigzag])", name).into()),
+    };
+
+    let transform = delta::Delta::new(mode, width, stride);
+    Ok(if zigzag { transform.with_zigzag() } else { transform })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_named_algorithm_roundtrips() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        for name in ALGORITHM_NAMES {
+            let codec = algorithm_from_name(name).unwrap();
+            for data in [&b""[..], b"x", &all_bytes] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_specs_fail() {
+        for spec in ["zip", "", "rle:2", "rle+", "+rle", "rle+zip"] {
+            assert!(algorithm_from_spec(spec).is_err(), "{:?}", spec);
+        }
+    }
+}
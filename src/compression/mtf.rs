@@ -0,0 +1,179 @@
+use crate::compression::CompressionAlgorithm;
+
+/// How a symbol moves in the recency list after it is coded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MtfVariant {
+    /// Classic move-to-front: the coded symbol always moves to position 0
+    MoveToFront,
+    /// Move-one-from-front: a symbol at position 1 moves to the front, any symbol
+    /// further back only moves to position 1. Protects the front symbol from
+    /// one-off interruptions, which helps on BWT output.
+    MoveOneFromFront,
+}
+
+impl MtfVariant {
+    fn id(self) -> u8 {
+        match self {
+            MtfVariant::MoveToFront => 0,
+            MtfVariant::MoveOneFromFront => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, Box<dyn std::error::Error>> {
+        match id {
+            0 => Ok(MtfVariant::MoveToFront),
+            1 => Ok(MtfVariant::MoveOneFromFront),
+            _ => Err(format!("Unknown MTF variant {}", id).into()),
+        }
+    }
+}
+
+/// Move-to-front transform.
+///
+/// Each byte is replaced by its position in a list of recently seen bytes, so
+/// clustered symbols (such as BWT output) become runs of small numbers and zeros.
+///
+/// Stream format: variant u8, then one output byte per input byte.
+pub struct MoveToFront {
+    variant: MtfVariant,
+}
+
+impl MoveToFront {
+    pub fn new(variant: MtfVariant) -> Self {
+        MoveToFront { variant }
+    }
+
+    fn initial_list() -> [u8; 256] {
+        let mut list = [0u8; 256];
+        for (i, entry) in list.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+        list
+    }
+
+    /// Move the symbol found at `index` according to the variant
+    fn update(list: &mut [u8; 256], index: usize, variant: MtfVariant) {
+        let target = match variant {
+            MtfVariant::MoveToFront => 0,
+            MtfVariant::MoveOneFromFront if index <= 1 => 0,
+            MtfVariant::MoveOneFromFront => 1,
+        };
+        if index > target {
+            let symbol = list[index];
+            list.copy_within(target..index, target + 1);
+            list[target] = symbol;
+        }
+    }
+
+    /// Forward transform without the variant header
+    pub fn encode(data: &[u8], variant: MtfVariant) -> Vec<u8> {
+        let mut list = Self::initial_list();
+        let mut output = Vec::with_capacity(data.len());
+
+        for &byte in data {
+            let index = list.iter().position(|&b| b == byte).unwrap_or(0);
+            output.push(index as u8);
+            Self::update(&mut list, index, variant);
+        }
+
+        output
+    }
+
+    /// Inverse transform without the variant header
+    pub fn decode(data: &[u8], variant: MtfVariant) -> Vec<u8> {
+        let mut list = Self::initial_list();
+        let mut output = Vec::with_capacity(data.len());
+
+        for &index in data {
+            let index = index as usize;
+            output.push(list[index]);
+            Self::update(&mut list, index, variant);
+        }
+
+        output
+    }
+}
+
+impl Default for MoveToFront {
+    fn default() -> Self {
+        Self::new(MtfVariant::MoveToFront)
+    }
+}
+
+impl CompressionAlgorithm for MoveToFront {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        eprintln!("Starting {:?} transform on {} bytes of data", self.variant, data.len());
+
+        let mut output = Vec::with_capacity(data.len() + 1);
+        output.push(self.variant.id());
+        output.extend_from_slice(&Self::encode(data, self.variant));
+
+        let zeros = output[1..].iter().filter(|&&b| b == 0).count();
+        eprintln!("MTF transform completed! {:.1}% of output bytes are zero",
+                 zeros as f64 / data.len() as f64 * 100.0);
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The stream records its own variant, so any MoveToFront can decode it
+        let variant = MtfVariant::from_id(data[0])?;
+        eprintln!("Starting inverse {:?} transform on {} bytes of data", variant, data.len() - 1);
+
+        Ok(Self::decode(&data[1..], variant))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARIANTS: [MtfVariant; 2] = [MtfVariant::MoveToFront, MtfVariant::MoveOneFromFront];
+
+    #[test]
+    fn roundtrip_both_variants() {
+        let all_bytes: Vec<u8> = (0..=255).rev().collect();
+        let text = b"bananas and banana bread".repeat(20);
+        for variant in VARIANTS {
+            let codec = MoveToFront::new(variant);
+            for data in [&b""[..], b"x", &all_bytes, &text] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data, "{:?}", variant);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_streams_decode_a_prefix() {
+        let data = b"mississippi river".repeat(10);
+        for variant in VARIANTS {
+            let codec = MoveToFront::new(variant);
+            let compressed = codec.compress(&data).unwrap();
+            for length in 0..compressed.len() {
+                assert!(data.starts_with(&codec.decompress(&compressed[..length]).unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_variant_fails() {
+        assert!(MoveToFront::default().decompress(&[7, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn variant_comes_from_the_spec() {
+        let data = b"bananas and banana bread".repeat(20);
+        let from_spec = crate::compression::algorithm_from_spec("mtf:m1ff").unwrap();
+        let m1ff = MoveToFront::new(MtfVariant::MoveOneFromFront);
+        assert_eq!(from_spec.compress(&data).unwrap(), m1ff.compress(&data).unwrap());
+        assert!(crate::compression::algorithm_from_spec("mtf:m2ff").is_err());
+    }
+}
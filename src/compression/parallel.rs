@@ -0,0 +1,213 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::compression::{CompressionAlgorithm, Progress};
+
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// Splits the input into independent blocks and compresses them on several
+/// threads with the wrapped codec. Blocks share no state, so the ratio is a
+/// little worse than a single pass but throughput scales with core count.
+///
+/// Stream format:
+///   block size   u32 (LE)
+///   block count  u32 (LE)
+///   block index  compressed size u32 (LE) per block
+///   compressed blocks, back to back
+pub struct BlockParallel {
+    inner: Box<dyn CompressionAlgorithm>,
+    block_size: usize,
+    threads: usize,
+    progress: Option<Progress>,
+}
+
+impl BlockParallel {
+    /// `threads == 0` uses every available core
+    pub fn new(inner: Box<dyn CompressionAlgorithm>, block_size: usize, threads: usize) -> Self {
+        BlockParallel { inner, block_size, threads, progress: None }
+    }
+
+    /// Report the input bytes of each finished block to `progress`
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    fn thread_count(&self) -> usize {
+        if self.threads == 0 { available_threads() } else { self.threads }
+    }
+}
+
+/// Number of threads the machine can run in parallel
+pub fn available_threads() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Run `job` for every index in `0..count` on up to `threads` worker threads,
+/// returning the results in index order. Workers take the next unclaimed index
+/// until none remain, so uneven jobs still balance.
+pub fn run_parallel<T, F>(count: usize, threads: usize, job: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..count).map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, count.max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count {
+                    break;
+                }
+                let result = job(index);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every block is processed"))
+        .collect()
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    let bytes = data.get(pos..pos + 4).ok_or("Truncated block index")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// Read the block size and block count from the start of a `BlockParallel` stream
+pub fn read_layout(data: &[u8]) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    Ok((read_u32(data, 0)?, read_u32(data, 4)?))
+}
+
+/// `run_parallel` over `blocks`, reporting the bytes of each finished block to
+/// `progress`
+pub fn run_blocks<T, F>(blocks: &[&[u8]], threads: usize, progress: Option<&Progress>, job: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let total: u64 = blocks.iter().map(|block| block.len() as u64).sum();
+    let done = AtomicU64::new(0);
+    run_parallel(blocks.len(), threads, |index| {
+        let result = job(index);
+        if let Some(progress) = progress {
+            let len = blocks[index].len() as u64;
+            progress.report(done.fetch_add(len, Ordering::Relaxed) + len, total);
+        }
+        result
+    })
+}
+
+/// Split a `BlockParallel` stream into its compressed blocks
+pub fn read_blocks(data: &[u8]) -> Result<Vec<&[u8]>, Box<dyn std::error::Error>> {
+    let block_count = read_u32(data, 4)?;
+    let mut pos = 8;
+
+    let mut blocks = Vec::with_capacity(block_count.min(data.len()));
+    let mut offset = 8 + block_count.checked_mul(4).ok_or("Invalid block count")?;
+    for _ in 0..block_count {
+        let size = read_u32(data, pos)?;
+        pos += 4;
+        let block = data.get(offset..offset + size).ok_or("Truncated block data")?;
+        blocks.push(block);
+        offset += size;
+    }
+    Ok(blocks)
+}
+
+impl CompressionAlgorithm for BlockParallel {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if self.block_size == 0 || self.block_size > u32::MAX as usize {
+            return Err(format!("Invalid block size: {}", self.block_size).into());
+        }
+
+        let blocks: Vec<&[u8]> = data.chunks(self.block_size).collect();
+        let threads = self.thread_count();
+        eprintln!("Compressing {} blocks of up to {} bytes on {} threads", blocks.len(), self.block_size, threads);
+
+        // Errors are turned into strings so they can leave the worker threads
+        let compressed = run_blocks(&blocks, threads, self.progress.as_ref(), |index| {
+            self.inner.compress(blocks[index]).map_err(|e| format!("Block {}: {}", index, e))
+        });
+
+        let mut output = Vec::new();
+        output.extend_from_slice(&(self.block_size as u32).to_le_bytes());
+        output.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+
+        let compressed = compressed.into_iter().collect::<Result<Vec<_>, _>>()?;
+        for block in &compressed {
+            if block.len() > u32::MAX as usize {
+                return Err("Compressed block too large for the block index".into());
+            }
+            output.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        }
+        for block in compressed {
+            output.extend_from_slice(&block);
+        }
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let blocks = read_blocks(data)?;
+        let threads = self.thread_count();
+        eprintln!("Decompressing {} blocks on {} threads", blocks.len(), threads);
+
+        let decompressed = run_blocks(&blocks, threads, self.progress.as_ref(), |index| {
+            self.inner.decompress(blocks[index]).map_err(|e| format!("Block {}: {}", index, e))
+        });
+
+        let mut output = Vec::new();
+        for block in decompressed {
+            output.extend_from_slice(&block?);
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::huffman::HuffmanCoding;
+
+    fn codec(block_size: usize, threads: usize) -> BlockParallel {
+        BlockParallel::new(Box::new(HuffmanCoding::default()), block_size, threads)
+    }
+
+    #[test]
+    fn roundtrip_block_sizes() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let text = b"each block gets its own thread ".repeat(100);
+        for (block_size, threads) in [(1, 1), (100, 3), (1000, 0), (DEFAULT_BLOCK_SIZE, 2)] {
+            let codec = codec(block_size, threads);
+            for data in [&b""[..], b"x", &all_bytes, &text] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(read_layout(&compressed).unwrap(), (block_size, data.len().div_ceil(block_size)));
+                assert_eq!(codec.decompress(&compressed).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let data = b"each block gets its own thread ".repeat(100);
+        let codec = codec(500, 2);
+        let compressed = codec.compress(&data).unwrap();
+        for length in 0..compressed.len() {
+            assert!(codec.decompress(&compressed[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn huge_block_count_fails() {
+        let mut compressed = codec(100, 1).compress(b"abc").unwrap();
+        compressed[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(codec(100, 1).decompress(&compressed).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::compression::{algorithm_from_name_with_options, CodecOptions, CompressionAlgorithm};
+
+/// Separator between stage names in a pipeline spec, e.g. `rle+lz77+huffman`
+pub const STAGE_SEPARATOR: char = '+';
+
+/// A chain of codecs applied one after another.
+///
+/// Compression runs the stages in order, each stage consuming the previous
+/// stage's output. Decompression runs them in reverse. The pipeline itself adds
+/// no framing; the stage list is recorded in the container header so the file
+/// can be decoded without repeating the spec.
+pub struct Pipeline {
+    stages: Vec<(String, Box<dyn CompressionAlgorithm>)>,
+}
+
+impl Pipeline {
+    /// Create an empty pipeline (behaves as the identity transform)
+    pub fn new() -> Self {
+        Pipeline { stages: Vec::new() }
+    }
+
+    /// Append a stage to the end of the pipeline
+    pub fn with_stage(mut self, name: impl Into<String>, stage: Box<dyn CompressionAlgorithm>) -> Self {
+        self.stages.push((name.into(), stage));
+        self
+    }
+
+    /// Build a pipeline from a spec such as `rle+lz77+huffman`
+    pub fn from_spec(spec: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_spec_with_options(spec, &CodecOptions::default())
+    }
+
+    /// Like `from_spec`, applying `options` to every stage
+    pub fn from_spec_with_options(spec: &str, options: &CodecOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut pipeline = Pipeline::new();
+
+        for name in spec.split(STAGE_SEPARATOR) {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(format!("Empty stage in pipeline '{}'", spec).into());
+            }
+            let stage = algorithm_from_name_with_options(name, options)
+                .map_err(|e| format!("{} (in pipeline '{}')", e, spec))?;
+            pipeline = pipeline.with_stage(name, stage);
+        }
+
+        Ok(pipeline)
+    }
+
+    /// The spec string describing this pipeline, e.g. `rle+lz77+huffman`
+    pub fn spec(&self) -> String {
+        self.stages
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(&STAGE_SEPARATOR.to_string())
+    }
+
+    /// Names of the stages in the order they are applied on compression
+    pub fn stage_names(&self) -> impl Iterator<Item = &str> {
+        self.stages.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompressionAlgorithm for Pipeline {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut current = data.to_vec();
+
+        for (name, stage) in &self.stages {
+            current = stage
+                .compress(&current)
+                .map_err(|e| format!("Pipeline stage '{}' failed to compress: {}", name, e))?;
+        }
+
+        Ok(current)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut current = data.to_vec();
+
+        for (name, stage) in self.stages.iter().rev() {
+            current = stage
+                .decompress(&current)
+                .map_err(|e| format!("Pipeline stage '{}' failed to decompress: {}", name, e))?;
+        }
+
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::huffman::HuffmanCoding;
+    use crate::compression::rle::RunLengthEncoding;
+
+    const SPECS: [&str; 3] = ["rle+lz77+huffman", "lz77+huffman", "rle+huffman"];
+
+    fn text() -> Vec<u8> {
+        b"pipelines chain codecs; codecs chain bytes. ".repeat(50)
+    }
+
+    #[test]
+    fn roundtrip_specs() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        for spec in SPECS {
+            let pipeline = Pipeline::from_spec(spec).unwrap();
+            assert_eq!(pipeline.spec(), spec);
+            for data in [&b""[..], b"x", &all_bytes, &text()] {
+                let compressed = pipeline.compress(data).unwrap();
+                assert_eq!(pipeline.decompress(&compressed).unwrap(), data, "{}", spec);
+            }
+        }
+    }
+
+    #[test]
+    fn stages_are_reversed_on_decompress() {
+        let data = text();
+        let pipeline = Pipeline::from_spec("rle+huffman").unwrap();
+        let by_hand = HuffmanCoding::default().compress(&RunLengthEncoding.compress(&data).unwrap()).unwrap();
+        assert_eq!(pipeline.decompress(&by_hand).unwrap(), data);
+        let compressed = pipeline.compress(&data).unwrap();
+        let stage_one = HuffmanCoding::default().decompress(&compressed).unwrap();
+        assert_eq!(RunLengthEncoding.decompress(&stage_one).unwrap(), data);
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let data = text();
+        for spec in SPECS {
+            let pipeline = Pipeline::from_spec(spec).unwrap();
+            let compressed = pipeline.compress(&data).unwrap();
+            for length in 1..compressed.len() {
+                if let Ok(decoded) = pipeline.decompress(&compressed[..length]) {
+                    assert_eq!(decoded, data, "{} length {}", spec, length);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bad_specs_fail() {
+        for spec in ["rle++huffman", "rle+nope", "huffman+", "+huffman"] {
+            assert!(Pipeline::from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// How many input bytes a codec works through between progress reports
+pub const REPORT_INTERVAL: usize = 64 * 1024;
+
+/// Callback told how far a codec has got, as (bytes processed, total bytes).
+/// It is cheap to clone and may be called from worker threads, so it must be
+/// `Send + Sync`. The last call has `processed == total`.
+///
+/// Codecs that can take a long time on a single stream (LZ77) report as they
+/// go; `BlockParallel` and `Seekable` report once per finished block.
+#[derive(Clone)]
+pub struct Progress(Arc<dyn Fn(u64, u64) + Send + Sync>);
+
+impl Progress {
+    pub fn new(callback: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        Progress(Arc::new(callback))
+    }
+
+    pub fn report(&self, processed: u64, total: u64) {
+        (self.0)(processed, total)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Progress(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::compression::huffman::HuffmanCoding;
+    use crate::compression::lz77::LZ77;
+    use crate::compression::{BlockParallel, CompressionAlgorithm};
+
+    type Reports = Arc<Mutex<Vec<(u64, u64)>>>;
+
+    fn recorder() -> (Progress, Reports) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        (Progress::new(move |processed, total| sink.lock().unwrap().push((processed, total))), reports)
+    }
+
+    fn check(reports: &[(u64, u64)], total: u64, count: usize) {
+        assert_eq!(reports.len(), count);
+        assert!(reports.iter().all(|&(_, reported)| reported == total));
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(reports.last(), Some(&(total, total)));
+    }
+
+    #[test]
+    fn lz77_reports_as_it_goes() {
+        let data: Vec<u8> = (0..50_000u32).flat_map(|i| (i % 1000).to_le_bytes()).collect();
+        let (progress, reports) = recorder();
+        LZ77::new().with_progress(progress).compress(&data).unwrap();
+        check(&reports.lock().unwrap(), data.len() as u64, data.len().div_ceil(REPORT_INTERVAL));
+    }
+
+    #[test]
+    fn blocks_report_when_done() {
+        let data = b"one report per block ".repeat(1000);
+        let (progress, reports) = recorder();
+        BlockParallel::new(Box::new(HuffmanCoding::default()), 4096, 3).with_progress(progress).compress(&data).unwrap();
+        check(&reports.lock().unwrap(), data.len() as u64, data.len().div_ceil(4096));
+    }
+}
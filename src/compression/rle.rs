@@ -1,5 +1,22 @@
+use std::collections::BTreeMap;
+
 use crate::compression::CompressionAlgorithm;
 
+/// Token counts of an RLE stream, from `RunLengthEncoding::run_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// Bytes copied through as-is
+    pub literals: usize,
+    /// Single 0xFF bytes, escaped as `0xFF 0x00 0xFF`
+    pub escapes: usize,
+    /// `0xFF <count> <byte>` runs
+    pub runs: usize,
+    /// Bytes produced by runs
+    pub run_bytes: usize,
+    /// Number of runs of each length
+    pub lengths: BTreeMap<u8, usize>,
+}
+
 pub struct RunLengthEncoding;
 
 impl RunLengthEncoding {
@@ -30,6 +47,26 @@ impl RunLengthEncoding {
         }
     }
 
+    /// Count the literals, escapes and runs of a compressed stream
+    pub fn run_stats(data: &[u8]) -> Result<RunStats, Box<dyn std::error::Error>> {
+        let mut stats = RunStats::default();
+        let mut pos = 0;
+        while pos < data.len() {
+            let start = pos;
+            let (byte, count) = Self::decode_next(data, &mut pos)?;
+            if pos - start == 1 {
+                stats.literals += 1;
+            } else if count == 1 && byte == 0xFF && data[start + 1] == 0x00 {
+                stats.escapes += 1;
+            } else {
+                stats.runs += 1;
+                stats.run_bytes += count as usize;
+                *stats.lengths.entry(count).or_default() += 1;
+            }
+        }
+        Ok(stats)
+    }
+
     /// Decode the next token from the input
     fn decode_next(data: &[u8], pos: &mut usize) -> Result<(u8, u8), Box<dyn std::error::Error>> {
         if *pos >= data.len() {
@@ -81,7 +118,7 @@ impl CompressionAlgorithm for RunLengthEncoding {
             return Ok(Vec::new());
         }
 
-        println!("Starting RLE compression on {} bytes of data", data.len());
+        eprintln!("Starting RLE compression on {} bytes of data", data.len());
 
         let mut output = Vec::new();
         let mut i = 0;
@@ -104,11 +141,11 @@ impl CompressionAlgorithm for RunLengthEncoding {
         }
 
         let compression_ratio = (output.len() as f64 / data.len() as f64) * 100.0;
-        println!("RLE compression completed!");
-        println!("Original size: {} bytes", data.len());
-        println!("Compressed size: {} bytes", output.len());
-        println!("Compression ratio: {:.1}%", compression_ratio);
-        println!("Space saved: {:.1}%", 100.0 - compression_ratio);
+        eprintln!("RLE compression completed!");
+        eprintln!("Original size: {} bytes", data.len());
+        eprintln!("Compressed size: {} bytes", output.len());
+        eprintln!("Compression ratio: {:.1}%", compression_ratio);
+        eprintln!("Space saved: {:.1}%", 100.0 - compression_ratio);
 
         Ok(output)
     }
@@ -118,7 +155,7 @@ impl CompressionAlgorithm for RunLengthEncoding {
             return Ok(Vec::new());
         }
 
-        println!("Starting RLE decompression on {} bytes of compressed data", data.len());
+        eprintln!("Starting RLE decompression on {} bytes of compressed data", data.len());
 
         let mut output = Vec::new();
         let mut pos = 0;
@@ -132,9 +169,44 @@ impl CompressionAlgorithm for RunLengthEncoding {
             }
         }
 
-        println!("RLE decompression completed!");
-        println!("Decompressed {} bytes to {} bytes", data.len(), output.len());
+        eprintln!("RLE decompression completed!");
+        eprintln!("Decompressed {} bytes to {} bytes", data.len(), output.len());
 
         Ok(output)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let mut runs = vec![0xFF; 600];
+        runs.extend([b'a'; 300]);
+        runs.extend(b"abcc\xFF\xFFd");
+        for data in [&b""[..], b"x", b"\xFF", &all_bytes, &runs] {
+            let compressed = RunLengthEncoding.compress(data).unwrap();
+            assert_eq!(RunLengthEncoding.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn truncated_streams_decode_a_prefix() {
+        let mut data = b"aaaaaaaabbbb\xFFcd".repeat(20);
+        data.extend([0xFF; 10]);
+        let compressed = RunLengthEncoding.compress(&data).unwrap();
+        for length in 0..compressed.len() {
+            if let Ok(decoded) = RunLengthEncoding.decompress(&compressed[..length]) {
+                assert!(data.starts_with(&decoded));
+            }
+        }
+    }
+
+    #[test]
+    fn incomplete_run_fails() {
+        assert!(RunLengthEncoding.decompress(&[b'a', 0xFF]).is_err());
+        assert!(RunLengthEncoding.decompress(&[0xFF, 5]).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::compression::parallel::{available_threads, run_blocks};
+use crate::compression::{CompressionAlgorithm, Progress};
+
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 16;
+
+/// Marks the end of a seekable stream
+pub const FOOTER_MAGIC: &[u8; 4] = b"PPSX";
+
+const ENTRY_SIZE: usize = 20;
+const FOOTER_SIZE: usize = 16;
+
+/// Compresses fixed-size blocks independently and appends an index of where
+/// each one starts, so a `SeekableReader` can decompress any byte range by
+/// touching only the blocks that cover it.
+///
+/// Stream format:
+///   compressed blocks, back to back
+///   index        per block: uncompressed offset u64, compressed offset u64,
+///                compressed size u32 (LE)
+///   footer       uncompressed length u64, block count u32, "PPSX"
+///
+/// The index sits at the end so the stream can be written in one pass and
+/// found again by seeking back from the end of the file.
+pub struct Seekable {
+    inner: Box<dyn CompressionAlgorithm>,
+    block_size: usize,
+    threads: usize,
+    progress: Option<Progress>,
+}
+
+impl Seekable {
+    /// `threads == 0` uses every available core
+    pub fn new(inner: Box<dyn CompressionAlgorithm>, block_size: usize, threads: usize) -> Self {
+        Seekable { inner, block_size, threads, progress: None }
+    }
+
+    /// Report the input bytes of each finished block to `progress`
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+/// Where one block lives in the compressed and uncompressed streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub uncompressed_offset: u64,
+    pub compressed_offset: u64,
+    pub compressed_size: u32,
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(value)
+}
+
+/// Decode the footer into (uncompressed length, block count)
+fn parse_footer(footer: &[u8]) -> Result<(u64, usize), Box<dyn std::error::Error>> {
+    if footer.len() != FOOTER_SIZE || &footer[12..] != FOOTER_MAGIC {
+        return Err("Not a seekable stream (missing index footer)".into());
+    }
+    Ok((read_u64(&footer[0..8]), read_u32(&footer[8..12]) as usize))
+}
+
+/// Decode the index and check that it describes `length` bytes stored in the
+/// first `data_len` bytes of the stream
+fn parse_index(
+    bytes: &[u8],
+    length: u64,
+    data_len: u64,
+) -> Result<Vec<IndexEntry>, Box<dyn std::error::Error>> {
+    let index: Vec<IndexEntry> = bytes
+        .chunks_exact(ENTRY_SIZE)
+        .map(|entry| IndexEntry {
+            uncompressed_offset: read_u64(&entry[0..8]),
+            compressed_offset: read_u64(&entry[8..16]),
+            compressed_size: read_u32(&entry[16..20]),
+        })
+        .collect();
+
+    let mut expected_offset = 0;
+    for (i, entry) in index.iter().enumerate() {
+        let next = index.get(i + 1).map_or(length, |next| next.uncompressed_offset);
+        if entry.uncompressed_offset != expected_offset {
+            return Err(format!("Seekable index entry {} has offset {}, expected {}", i, entry.uncompressed_offset, expected_offset).into());
+        }
+        if next <= entry.uncompressed_offset {
+            return Err(format!("Seekable index entry {} is empty or out of order", i).into());
+        }
+        let end = entry.compressed_offset.checked_add(entry.compressed_size as u64);
+        if end.is_none_or(|end| end > data_len) {
+            return Err(format!("Seekable index entry {} points past the block data", i).into());
+        }
+        expected_offset = next;
+    }
+    if expected_offset != length {
+        return Err("Seekable index does not cover the uncompressed length".into());
+    }
+
+    Ok(index)
+}
+
+/// Read the uncompressed length and block index of a seekable stream held in memory
+pub fn read_index(data: &[u8]) -> Result<(u64, Vec<IndexEntry>), Box<dyn std::error::Error>> {
+    if data.len() < FOOTER_SIZE {
+        return Err("Not a seekable stream (missing index footer)".into());
+    }
+    let (length, count) = parse_footer(&data[data.len() - FOOTER_SIZE..])?;
+    let index_start = count
+        .checked_mul(ENTRY_SIZE)
+        .and_then(|size| (data.len() - FOOTER_SIZE).checked_sub(size))
+        .ok_or("Truncated seekable index")?;
+    let index = parse_index(&data[index_start..data.len() - FOOTER_SIZE], length, index_start as u64)?;
+    Ok((length, index))
+}
+
+impl CompressionAlgorithm for Seekable {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if self.block_size == 0 {
+            return Err("Invalid block size: 0".into());
+        }
+
+        let blocks: Vec<&[u8]> = data.chunks(self.block_size).collect();
+        let threads = if self.threads == 0 { available_threads() } else { self.threads };
+        eprintln!("Compressing {} seekable blocks of up to {} bytes", blocks.len(), self.block_size);
+
+        let compressed = run_blocks(&blocks, threads, self.progress.as_ref(), |index| {
+            self.inner.compress(blocks[index]).map_err(|e| format!("Block {}: {}", index, e))
+        });
+
+        let mut output = Vec::new();
+        let mut index = Vec::with_capacity(blocks.len());
+        for (i, block) in compressed.into_iter().enumerate() {
+            let block = block?;
+            if block.len() > u32::MAX as usize {
+                return Err("Compressed block too large for the seekable index".into());
+            }
+            index.push(IndexEntry {
+                uncompressed_offset: (i * self.block_size) as u64,
+                compressed_offset: output.len() as u64,
+                compressed_size: block.len() as u32,
+            });
+            output.extend_from_slice(&block);
+        }
+
+        for entry in &index {
+            output.extend_from_slice(&entry.uncompressed_offset.to_le_bytes());
+            output.extend_from_slice(&entry.compressed_offset.to_le_bytes());
+            output.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        }
+        output.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        output.extend_from_slice(&(index.len() as u32).to_le_bytes());
+        output.extend_from_slice(FOOTER_MAGIC);
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (length, index) = read_index(data)?;
+
+        let blocks: Vec<&[u8]> = index
+            .iter()
+            .map(|entry| {
+                let start = entry.compressed_offset as usize;
+                &data[start..start + entry.compressed_size as usize]
+            })
+            .collect();
+
+        let threads = if self.threads == 0 { available_threads() } else { self.threads };
+        let decompressed = run_blocks(&blocks, threads, self.progress.as_ref(), |i| {
+            self.inner.decompress(blocks[i]).map_err(|e| format!("Block {}: {}", i, e))
+        });
+
+        // Sized from the blocks themselves; the footer's length is not trusted
+        // until it has been checked against them
+        let output = decompressed.into_iter().collect::<Result<Vec<_>, _>>()?.concat();
+        if output.len() as u64 != length {
+            return Err("Seekable stream decompressed to the wrong length".into());
+        }
+        Ok(output)
+    }
+}
+
+/// Random-access reader over a seekable stream. Only the blocks covering the
+/// bytes actually read are decompressed; the most recent block is cached so
+/// sequential reads decompress each block once.
+///
+/// The stream starts at the reader's position when `new` is called, so a
+/// reader positioned after a container header works as-is.
+pub struct SeekableReader<R> {
+    reader: R,
+    inner: Box<dyn CompressionAlgorithm>,
+    start: u64,
+    index: Vec<IndexEntry>,
+    length: u64,
+    position: u64,
+    cache: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableReader<R> {
+    /// Read the index of the stream in `reader`, whose blocks were compressed with `inner`
+    pub fn new(mut reader: R, inner: Box<dyn CompressionAlgorithm>) -> Result<Self, Box<dyn std::error::Error>> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < start + FOOTER_SIZE as u64 {
+            return Err("Not a seekable stream (missing index footer)".into());
+        }
+
+        let mut footer = [0u8; FOOTER_SIZE];
+        reader.seek(SeekFrom::Start(end - FOOTER_SIZE as u64))?;
+        reader.read_exact(&mut footer)?;
+        let (length, count) = parse_footer(&footer)?;
+
+        let index_size = (count as u64).checked_mul(ENTRY_SIZE as u64).ok_or("Truncated seekable index")?;
+        let data_len = (end - start - FOOTER_SIZE as u64).checked_sub(index_size).ok_or("Truncated seekable index")?;
+        let mut index_bytes = vec![0u8; index_size as usize];
+        reader.seek(SeekFrom::Start(start + data_len))?;
+        reader.read_exact(&mut index_bytes)?;
+        let index = parse_index(&index_bytes, length, data_len)?;
+
+        Ok(SeekableReader { reader, inner, start, index, length, position: 0, cache: None })
+    }
+
+    /// Uncompressed length of the stream
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The block index read from the end of the stream
+    pub fn index(&self) -> &[IndexEntry] {
+        &self.index
+    }
+
+    /// Decompress block `block` unless it is already cached
+    fn load_block(&mut self, block: usize) -> io::Result<&[u8]> {
+        if self.cache.as_ref().map(|(cached, _)| *cached) != Some(block) {
+            let entry = self.index[block];
+            let mut compressed = vec![0u8; entry.compressed_size as usize];
+            self.reader.seek(SeekFrom::Start(self.start + entry.compressed_offset))?;
+            self.reader.read_exact(&mut compressed)?;
+
+            let decompressed = self
+                .inner
+                .decompress(&compressed)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Block {}: {}", block, e)))?;
+            let expected = self.index.get(block + 1).map_or(self.length, |next| next.uncompressed_offset)
+                - entry.uncompressed_offset;
+            if decompressed.len() as u64 != expected {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Block {} has the wrong length", block)));
+            }
+            self.cache = Some((block, decompressed));
+        }
+        Ok(&self.cache.as_ref().unwrap().1)
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.length {
+            return Ok(0);
+        }
+
+        let position = self.position;
+        let block = self.index.partition_point(|entry| entry.uncompressed_offset <= position) - 1;
+        let block_start = self.index[block].uncompressed_offset;
+        let data = self.load_block(block)?;
+
+        let offset = (position - block_start) as usize;
+        let count = buf.len().min(data.len() - offset);
+        buf[..count].copy_from_slice(&data[offset..offset + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.length.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        match target {
+            Some(target) => {
+                self.position = target;
+                Ok(target)
+            }
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "Seek to a negative or overflowing position")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::compression::lz77::LZ77;
+
+    fn text() -> Vec<u8> {
+        (0..2000u32).flat_map(|i| format!("record {:05}\n", i).into_bytes()).collect()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let text = text();
+        for block_size in [100, 1000, DEFAULT_BLOCK_SIZE] {
+            let codec = Seekable::new(Box::new(LZ77::new()), block_size, 2);
+            for data in [&b""[..], b"x", &all_bytes, &text] {
+                let compressed = codec.compress(data).unwrap();
+                assert_eq!(codec.decompress(&compressed).unwrap(), data, "block size {}", block_size);
+                assert_eq!(read_index(&compressed).unwrap().0, data.len() as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn reader_reads_ranges() {
+        let text = text();
+        let compressed = Seekable::new(Box::new(LZ77::new()), 1000, 1).compress(&text).unwrap();
+        let mut reader = SeekableReader::new(Cursor::new(compressed), Box::new(LZ77::new())).unwrap();
+        assert_eq!(reader.len(), text.len() as u64);
+        assert_eq!(reader.index().len(), text.len().div_ceil(1000));
+        for (start, length) in [(0, 10), (995, 10), (12_345, 3000), (text.len() - 5, 5)] {
+            let mut buffer = vec![0u8; length];
+            reader.seek(SeekFrom::Start(start as u64)).unwrap();
+            reader.read_exact(&mut buffer).unwrap();
+            assert_eq!(buffer, &text[start..start + length]);
+        }
+        let mut rest = Vec::new();
+        reader.seek(SeekFrom::End(-3)).unwrap();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &text[text.len() - 3..]);
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let codec = Seekable::new(Box::new(LZ77::new()), 1000, 1);
+        let compressed = codec.compress(&text()[..5000]).unwrap();
+        for length in 0..compressed.len() {
+            assert!(codec.decompress(&compressed[..length]).is_err(), "length {}", length);
+            assert!(SeekableReader::new(Cursor::new(&compressed[..length]), Box::new(LZ77::new())).is_err());
+        }
+    }
+
+    #[test]
+    fn corrupt_index_fails() {
+        let codec = Seekable::new(Box::new(LZ77::new()), 1000, 1);
+        let compressed = codec.compress(&text()).unwrap();
+        let footer = compressed.len() - FOOTER_SIZE;
+        // Block count and uncompressed length
+        for index in [footer + 8, footer] {
+            let mut corrupt = compressed.clone();
+            corrupt[index] ^= 0x40;
+            assert!(codec.decompress(&corrupt).is_err(), "byte {}", index);
+        }
+        // A huge length is not allocated up front
+        let mut corrupt = compressed.clone();
+        corrupt[footer..footer + 8].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        assert!(codec.decompress(&corrupt).is_err());
+
+        // Moving where block 7 starts leaves block 6 the wrong length
+        let mut corrupt = compressed.clone();
+        corrupt[footer - ENTRY_SIZE * 19] ^= 0x40;
+        let mut reader = SeekableReader::new(Cursor::new(corrupt), Box::new(LZ77::new())).unwrap();
+        reader.seek(SeekFrom::Start(6500)).unwrap();
+        assert!(reader.read(&mut [0u8; 10]).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::compression::CompressionAlgorithm;
+
+// Output symbols for the bijective base-2 digits of a zero-run length
+const RUNA: u8 = 0x00;
+const RUNB: u8 = 0x01;
+// Prefix for the two byte values that do not fit after shifting by one
+const ESCAPE: u8 = 0xFF;
+
+/// bzip2-style zero-run encoding.
+///
+/// Runs of zero bytes are written as their length in bijective base 2 using two
+/// symbols, RUNA (digit 1) and RUNB (digit 2), least significant digit first, so
+/// a run of n zeros costs about log2(n) symbols. Non-zero bytes are shifted up by
+/// one to make room; 254 and 255 are written as `0xFF 0x00` and `0xFF 0x01`.
+///
+/// The stream starts with the original size (u32 LE) so the decoder can reject
+/// runs that reach past it instead of expanding a few corrupt digits into
+/// gigabytes of zeros.
+///
+/// Intended to run directly after MTF, whose output is dominated by zeros.
+pub struct ZeroRunEncoding;
+
+impl ZeroRunEncoding {
+    fn encode_run(output: &mut Vec<u8>, mut length: usize) {
+        while length > 0 {
+            if length & 1 == 1 {
+                output.push(RUNA);
+                length = (length - 1) / 2;
+            } else {
+                output.push(RUNB);
+                length = (length - 2) / 2;
+            }
+        }
+    }
+
+    fn encode_literal(output: &mut Vec<u8>, byte: u8) {
+        match byte {
+            254 => output.extend_from_slice(&[ESCAPE, 0x00]),
+            255 => output.extend_from_slice(&[ESCAPE, 0x01]),
+            _ => output.push(byte + 1),
+        }
+    }
+}
+
+impl CompressionAlgorithm for ZeroRunEncoding {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if data.len() > u32::MAX as usize {
+            return Err("Zero-run input too large (over 4 GiB)".into());
+        }
+
+        eprintln!("Starting zero-run encoding on {} bytes of data", data.len());
+
+        let mut output = Vec::with_capacity(data.len());
+        output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        let mut run = 0;
+
+        for &byte in data {
+            if byte == 0 {
+                run += 1;
+                continue;
+            }
+            Self::encode_run(&mut output, run);
+            run = 0;
+            Self::encode_literal(&mut output, byte);
+        }
+        Self::encode_run(&mut output, run);
+
+        eprintln!("Zero-run encoding completed! {} bytes -> {} bytes", data.len(), output.len());
+
+        Ok(output)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if data.len() < 4 {
+            return Err("Zero-run data too short for its header".into());
+        }
+        let original_size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+
+        eprintln!("Starting zero-run decoding on {} bytes of data", data.len());
+
+        let mut output = Vec::new();
+        let mut run = 0usize;
+        let mut digit_weight = 1usize;
+        let mut pos = 4;
+
+        while pos < data.len() {
+            let symbol = data[pos];
+            pos += 1;
+
+            if symbol == RUNA || symbol == RUNB {
+                let digit = if symbol == RUNA { 1 } else { 2 };
+                run = digit_weight
+                    .checked_mul(digit)
+                    .and_then(|value| run.checked_add(value))
+                    .filter(|&run| run <= original_size - output.len())
+                    .ok_or("Zero run goes past the original size")?;
+                digit_weight = digit_weight.checked_mul(2).ok_or("Zero run too long")?;
+                continue;
+            }
+
+            // A literal ends any pending run
+            output.resize(output.len() + run, 0);
+            run = 0;
+            digit_weight = 1;
+            if output.len() == original_size {
+                return Err("Zero-run data continues past the original size".into());
+            }
+
+            if symbol == ESCAPE {
+                let escaped = *data.get(pos).ok_or("Incomplete escape sequence")?;
+                pos += 1;
+                match escaped {
+                    0x00 => output.push(254),
+                    0x01 => output.push(255),
+                    _ => return Err("Invalid escape sequence".into()),
+                }
+            } else {
+                output.push(symbol - 1);
+            }
+        }
+        output.resize(output.len() + run, 0);
+        if output.len() != original_size {
+            return Err("Zero-run data ended early".into());
+        }
+
+        eprintln!("Zero-run decoding completed! {} bytes -> {} bytes", data.len(), output.len());
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let mut zeros = vec![0u8; 1000];
+        zeros.extend([254, 255, 0, 0, 1, 0]);
+        for data in [&b""[..], b"\0", b"x", &all_bytes, &zeros] {
+            let compressed = ZeroRunEncoding.compress(data).unwrap();
+            assert_eq!(ZeroRunEncoding.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn truncated_streams_fail() {
+        let mut data = vec![0u8; 700];
+        data.extend((0..=255).cycle().take(500));
+        data.extend([0u8; 300]);
+        let compressed = ZeroRunEncoding.compress(&data).unwrap();
+        for length in 1..compressed.len() {
+            assert!(ZeroRunEncoding.decompress(&compressed[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn corrupt_streams_fail() {
+        let size = 10u32.to_le_bytes();
+        assert!(ZeroRunEncoding.decompress(&[&size[..], &[ESCAPE]].concat()).is_err());
+        assert!(ZeroRunEncoding.decompress(&[&size[..], &[ESCAPE, 2]].concat()).is_err());
+        assert!(ZeroRunEncoding.decompress(&[&size[..], &[b'a'; 11]].concat()).is_err());
+    }
+
+    #[test]
+    fn long_runs_fail() {
+        // 33 RUNA digits describe a run of 8 GiB, past even the largest size
+        for size in [10, u32::MAX] {
+            let mut huge_run = size.to_le_bytes().to_vec();
+            huge_run.extend([RUNA; 33]);
+            assert!(ZeroRunEncoding.decompress(&huge_run).is_err(), "size {}", size);
+        }
+        // One zero too many
+        let mut one_over = 2u32.to_le_bytes().to_vec();
+        one_over.extend([RUNB, RUNA]);
+        assert!(ZeroRunEncoding.decompress(&one_over).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+// Container format written by the CLI around a codec's output.
+//
+// Layout (version 2):
+//   magic     4 bytes  "PIPR"
+//   version   1 byte
+//   flags     1 byte   FLAG_* bits below
+//   spec_len  1 byte
+//   spec      spec_len bytes, UTF-8 algorithm spec (e.g. "rle+lz77+huffman")
+//   checksum  4 bytes, CRC-32 (LE) of the original data, if FLAG_CHECKSUM is set
+//   file      if FLAG_FILE is set, the input file's modification time (i64 LE,
+//             seconds since the epoch), permission bits (u32 LE), name length
+//             (1 byte) and UTF-8 file name, restored on decompression
+//   payload   remaining bytes, the codec output
+//
+// Version 1 is the same without the flags byte and is still read.
+//
+// Recording the spec means a file can be decompressed without being told which
+// algorithm or pipeline produced it.
+//
+// Codecs with a standard file format of their own (LZW's `.Z`) are written
+// without a container so other tools can read them; they are recognised by
+// their own magic number instead.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::archive::FileMetadata;
+use crate::checksum::crc32;
+use crate::compression::pipeline::STAGE_SEPARATOR;
+use crate::compression::{algorithm_from_spec_with_options, lzw, CodecOptions, SeekableReader};
+
+pub const MAGIC: &[u8; 4] = b"PIPR";
+pub const VERSION: u8 = 2;
+
+// The payload is a `BlockParallel` stream of independently compressed blocks
+const FLAG_BLOCKS: u8 = 0x01;
+// The payload is a `Seekable` stream with a trailing block index
+const FLAG_SEEKABLE: u8 = 0x02;
+// A CRC-32 of the original data follows the spec
+const FLAG_CHECKSUM: u8 = 0x04;
+// The original file's name, permissions and mtime follow the checksum
+const FLAG_FILE: u8 = 0x08;
+
+// Longest possible header: the spec and file name lengths are stored in one byte
+const MAX_HEADER_LEN: usize = MAGIC.len() + 3 + u8::MAX as usize + 4 + 13 + u8::MAX as usize;
+
+/// The file that was compressed, as recorded in the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    /// Final path component; empty if it was not UTF-8 or too long to store
+    pub name: String,
+    pub metadata: FileMetadata,
+}
+
+impl FileInfo {
+    /// Read the name, permissions and modification time of the file at `path`
+    pub fn from_path(path: &Path) -> std::io::Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.len() <= u8::MAX as usize)
+            .unwrap_or_default();
+        Ok(FileInfo { name: name.to_string(), metadata: FileMetadata::from_path(path)? })
+    }
+
+    /// The recorded name if it is safe to create in the output directory: a
+    /// single path component that is not `.` or `..`
+    pub fn safe_name(&self) -> Option<&str> {
+        let name = self.name.as_str();
+        let unsafe_name = name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']);
+        (!unsafe_name).then_some(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub algorithm: String,
+    /// Whether the payload was split into blocks compressed in parallel
+    pub blocks: bool,
+    /// Whether the payload ends with a block index for random access
+    pub seekable: bool,
+    /// CRC-32 of the original data, checked after decompression
+    pub checksum: Option<u32>,
+    /// Name and metadata of the compressed file, if it came from one
+    pub file: Option<FileInfo>,
+}
+
+impl Header {
+    pub fn new(algorithm: impl Into<String>) -> Self {
+        Header { algorithm: algorithm.into(), blocks: false, seekable: false, checksum: None, file: None }
+    }
+
+    /// Mark the payload as a block-parallel stream
+    pub fn with_blocks(mut self) -> Self {
+        self.blocks = true;
+        self
+    }
+
+    /// Mark the payload as a seekable stream
+    pub fn with_seekable(mut self) -> Self {
+        self.seekable = true;
+        self
+    }
+
+    /// Record the CRC-32 of `data`, the uncompressed input
+    pub fn with_checksum(mut self, data: &[u8]) -> Self {
+        self.checksum = Some(crc32(data));
+        self
+    }
+
+    /// Record the name and metadata of the file the data was read from
+    pub fn with_file(mut self, file: FileInfo) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    /// Check decompressed `data` against the recorded checksum, if any
+    pub fn verify(&self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        match self.checksum {
+            Some(expected) if crc32(data) != expected => Err(format!(
+                "checksum mismatch (expected CRC-32 {:08x}, got {:08x})", expected, crc32(data)
+            ).into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.blocks {
+            flags |= FLAG_BLOCKS;
+        }
+        if self.seekable {
+            flags |= FLAG_SEEKABLE;
+        }
+        if self.checksum.is_some() {
+            flags |= FLAG_CHECKSUM;
+        }
+        if self.file.is_some() {
+            flags |= FLAG_FILE;
+        }
+        flags
+    }
+
+    /// Serialize the header into the output buffer
+    pub fn write(&self, output: &mut Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        let spec = self.algorithm.as_bytes();
+        if spec.len() > u8::MAX as usize {
+            return Err(format!("Algorithm spec too long for container header: {}", self.algorithm).into());
+        }
+
+        output.extend_from_slice(MAGIC);
+        output.push(VERSION);
+        output.push(self.flags());
+        output.push(spec.len() as u8);
+        output.extend_from_slice(spec);
+        if let Some(checksum) = self.checksum {
+            output.extend_from_slice(&checksum.to_le_bytes());
+        }
+        if let Some(file) = &self.file {
+            if file.name.len() > u8::MAX as usize {
+                return Err(format!("File name too long for container header: {}", file.name).into());
+            }
+            output.extend_from_slice(&file.metadata.mtime.to_le_bytes());
+            output.extend_from_slice(&file.metadata.mode.to_le_bytes());
+            output.push(file.name.len() as u8);
+            output.extend_from_slice(file.name.as_bytes());
+        }
+        Ok(())
+    }
+
+    /// Parse a header from the start of `data`, returning it with the payload offset.
+    /// Returns `Ok(None)` when `data` does not start with the container magic.
+    pub fn parse(data: &[u8]) -> Result<Option<(Header, usize)>, Box<dyn std::error::Error>> {
+        if !is_container(data) {
+            return Ok(None);
+        }
+
+        let mut pos = MAGIC.len();
+        let version = *data.get(pos).ok_or("Truncated container header")?;
+        pos += 1;
+        let flags = match version {
+            1 => 0,
+            VERSION => {
+                let flags = *data.get(pos).ok_or("Truncated container header")?;
+                pos += 1;
+                flags
+            }
+            _ => return Err(format!("Unsupported container version {}", version).into()),
+        };
+        if flags & !(FLAG_BLOCKS | FLAG_SEEKABLE | FLAG_CHECKSUM | FLAG_FILE) != 0 {
+            return Err(format!("Unknown container flags {:#04x}", flags).into());
+        }
+
+        let spec_len = *data.get(pos).ok_or("Truncated container header")? as usize;
+        pos += 1;
+        let spec = data.get(pos..pos + spec_len).ok_or("Truncated container header")?;
+        pos += spec_len;
+
+        let algorithm = String::from_utf8(spec.to_vec())
+            .map_err(|_| "Container header has a non UTF-8 algorithm spec")?;
+
+        let checksum = if flags & FLAG_CHECKSUM != 0 {
+            let bytes = data.get(pos..pos + 4).ok_or("Truncated container header")?;
+            pos += 4;
+            Some(u32::from_le_bytes(bytes.try_into()?))
+        } else {
+            None
+        };
+
+        let file = if flags & FLAG_FILE != 0 {
+            let fixed = data.get(pos..pos + 13).ok_or("Truncated container header")?;
+            let mtime = i64::from_le_bytes(fixed[..8].try_into()?);
+            let mode = u32::from_le_bytes(fixed[8..12].try_into()?);
+            let name_len = fixed[12] as usize;
+            pos += 13;
+            let name = data.get(pos..pos + name_len).ok_or("Truncated container header")?;
+            pos += name_len;
+            let name = String::from_utf8(name.to_vec()).map_err(|_| "Container header has a non UTF-8 file name")?;
+            Some(FileInfo { name, metadata: FileMetadata { mode, mtime } })
+        } else {
+            None
+        };
+
+        let header = Header {
+            algorithm,
+            blocks: flags & FLAG_BLOCKS != 0,
+            seekable: flags & FLAG_SEEKABLE != 0,
+            checksum,
+            file,
+        };
+        Ok(Some((header, pos)))
+    }
+}
+
+/// Check whether `data` starts with the container magic
+pub fn is_container(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Prefix `payload` with a container header naming `algorithm`
+pub fn wrap(algorithm: &str, payload: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    wrap_with_header(&Header::new(algorithm), payload)
+}
+
+/// Prefix `payload` with `header`
+pub fn wrap_with_header(header: &Header, payload: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut output = Vec::with_capacity(payload.len() + MAX_HEADER_LEN);
+    header.write(&mut output)?;
+    output.extend_from_slice(payload);
+    Ok(output)
+}
+
+/// Whether `algorithm` produces a standalone file format that is written as-is:
+/// only a single `lzw` stage, since a pipeline's output is not a `.Z` file
+pub fn is_native_format(algorithm: &str) -> bool {
+    !algorithm.contains(STAGE_SEPARATOR) && (algorithm == "lzw" || algorithm.starts_with("lzw:"))
+}
+
+/// Identify a file in a native format by its magic number
+pub fn detect_native_format(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&lzw::MAGIC) {
+        Some("lzw")
+    } else {
+        None
+    }
+}
+
+/// Open a seekable container file for random access. The codec is taken from
+/// the header; `options` supplies anything it needs that is not stored in the
+/// file, such as a preset dictionary.
+pub fn open_seekable<R: Read + Seek>(
+    mut reader: R,
+    options: &CodecOptions,
+) -> Result<SeekableReader<R>, Box<dyn std::error::Error>> {
+    let mut prefix = Vec::with_capacity(MAX_HEADER_LEN);
+    reader.seek(SeekFrom::Start(0))?;
+    reader.by_ref().take(MAX_HEADER_LEN as u64).read_to_end(&mut prefix)?;
+
+    let (header, offset) = Header::parse(&prefix)?.ok_or("Not a container file")?;
+    if !header.seekable {
+        return Err(format!("'{}' container was not written in seekable mode", header.algorithm).into());
+    }
+    let codec = algorithm_from_spec_with_options(&header.algorithm, options)?;
+
+    reader.seek(SeekFrom::Start(offset as u64))?;
+    SeekableReader::new(reader, codec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file() -> FileInfo {
+        FileInfo { name: "report.txt".to_string(), metadata: FileMetadata { mode: 0o600, mtime: -86_400 } }
+    }
+
+    fn headers() -> Vec<Header> {
+        vec![
+            Header::new("huffman"),
+            Header::new("rle+lz77+huffman"),
+            Header::new("lz77").with_blocks(),
+            Header::new("lz77").with_seekable(),
+            Header::new("rle+lz77+huffman").with_blocks().with_checksum(b"data"),
+            Header::new("bwt+mtf+zrle+rans").with_checksum(b""),
+            Header::new("lz77").with_seekable().with_file(file()),
+            Header::new("rle").with_checksum(b"").with_file(FileInfo { name: "é".repeat(127), ..file() }),
+        ]
+    }
+
+    #[test]
+    fn header_roundtrip() {
+        for header in headers() {
+            for payload in [&b""[..], b"x", &[0xFF; 256]] {
+                let wrapped = wrap_with_header(&header, payload).unwrap();
+                let (parsed, offset) = Header::parse(&wrapped).unwrap().unwrap();
+                assert_eq!(parsed, header);
+                assert_eq!(&wrapped[offset..], payload);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_headers_fail() {
+        for header in headers() {
+            let mut written = Vec::new();
+            header.write(&mut written).unwrap();
+            for length in MAGIC.len()..written.len() {
+                assert!(Header::parse(&written[..length]).is_err(), "length {}", length);
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_headers_fail() {
+        let wrapped = wrap_with_header(&headers()[2], b"payload").unwrap();
+        for (index, value) in [(4, 3), (5, 0x10)] {
+            let mut corrupt = wrapped.clone();
+            corrupt[index] = value;
+            assert!(Header::parse(&corrupt).is_err(), "byte {}", index);
+        }
+        assert!(Header::parse(b"not a container").unwrap().is_none());
+        assert!(Header::new("x".repeat(256)).write(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn checksum_is_verified() {
+        let header = Header::new("rle").with_checksum(b"original");
+        assert!(header.verify(b"original").is_ok());
+        assert!(header.verify(b"originaL").is_err());
+        assert!(Header::new("rle").verify(b"anything").is_ok());
+    }
+
+    #[test]
+    fn version_1_headers_are_read() {
+        let mut data = MAGIC.to_vec();
+        data.extend([1, 3]);
+        data.extend(b"rle");
+        data.extend(b"payload");
+        let (header, offset) = Header::parse(&data).unwrap().unwrap();
+        assert_eq!(header, Header::new("rle"));
+        assert_eq!(&data[offset..], b"payload");
+    }
+
+    #[test]
+    fn only_plain_names_are_safe() {
+        assert_eq!(file().safe_name(), Some("report.txt"));
+        for name in ["", ".", "..", "a/b", "a\\b", "nul\0"] {
+            let info = FileInfo { name: name.to_string(), ..file() };
+            assert_eq!(info.safe_name(), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn only_single_stage_lzw_is_native() {
+        assert!(is_native_format("lzw"));
+        assert!(is_native_format("lzw:12"));
+        assert!(!is_native_format("lzw+huffman"));
+        assert!(!is_native_format("lzw:12+huffman"));
+        assert!(!is_native_format("rle+lzw"));
+        assert!(!is_native_format("lz77"));
+    }
+}
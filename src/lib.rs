@@ -1,3 +1,7 @@
+pub mod archive;
+pub mod checksum;
+pub mod cli;
 pub mod compression;
+pub mod container;
 
 pub use compression::*;
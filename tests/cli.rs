@@ -0,0 +1,345 @@
+// End-to-end tests of the command line: each runs `cli::run` on files in a
+// scratch directory, as `main` would with the same arguments.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+
+use compression_algorithm::archive::FileMetadata;
+use compression_algorithm::cli::{self, Cli};
+use compression_algorithm::compression::auto;
+use compression_algorithm::container::Header;
+
+/// A directory under the system temp dir, removed when dropped
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("compression_algorithm-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Scratch(path)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// Write `data` to `name`, creating parent directories
+    fn write(&self, name: &str, data: &[u8]) -> PathBuf {
+        let path = self.path(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, data).unwrap();
+        path
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run_str(args: &[&str]) -> Result<(), Box<dyn Error>> {
+    cli::run(Cli::try_parse_from(std::iter::once("compression_algorithm").chain(args.iter().copied()))?)
+}
+
+fn text() -> Vec<u8> {
+    (0..3000u32).flat_map(|i| format!("{:05} the quick brown fox\n", i * 7).into_bytes()).collect()
+}
+
+fn p(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn compress_and_decompress() {
+    let scratch = Scratch::new("roundtrip");
+    let all_bytes: Vec<u8> = (0..=255).collect();
+    let inputs = [
+        scratch.write("empty", b""),
+        scratch.write("one", b"x"),
+        scratch.write("all", &all_bytes),
+        scratch.write("text", &text()),
+    ];
+    let compressed = scratch.path("out.pp");
+    let restored = scratch.path("out");
+
+    for spec in ["huffman", "rle+lz77+huffman", "bwt+mtf+zrle+rans", "lzw"] {
+        for input in &inputs {
+            run_str(&["compress", p(input), "-a", spec, "-o", p(&compressed)]).unwrap();
+            run_str(&["decompress", p(&compressed), "-o", p(&restored)]).unwrap();
+            assert_eq!(fs::read(&restored).unwrap(), fs::read(input).unwrap(), "{} on {}", spec, input.display());
+        }
+    }
+}
+
+
+#[test]
+fn compress_in_blocks() {
+    let scratch = Scratch::new("blocks");
+    let input = scratch.write("text", &text());
+    let compressed = scratch.path("text.pp");
+    let restored = scratch.path("restored");
+
+    run_str(&["compress", p(&input), "-a", "lz77", "-t", "3", "-b", "10000", "-o", p(&compressed)]).unwrap();
+    run_str(&["decompress", p(&compressed), "-t", "2", "-o", p(&restored)]).unwrap();
+    assert_eq!(fs::read(&restored).unwrap(), text());
+}
+
+
+#[test]
+fn corrupt_files_fail_to_decompress() {
+    let scratch = Scratch::new("corrupt");
+    let input = scratch.write("text", &text());
+    let compressed = scratch.path("text.pp");
+    let restored = scratch.path("restored");
+    run_str(&["compress", p(&input), "-a", "rle", "-o", p(&compressed)]).unwrap();
+
+    // RLE copies most bytes through, so only the container checksum notices
+    let mut data = fs::read(&compressed).unwrap();
+    let middle = data.len() / 2;
+    data[middle] ^= 0x01;
+    fs::write(&compressed, &data).unwrap();
+    let error = run_str(&["decompress", p(&compressed), "-o", p(&restored)]).unwrap_err();
+    assert!(error.to_string().contains("checksum"), "{}", error);
+
+    data.truncate(middle);
+    fs::write(&compressed, &data).unwrap();
+    assert!(run_str(&["decompress", p(&compressed), "-o", p(&restored)]).is_err());
+}
+
+
+#[test]
+fn verify_and_test() {
+    let scratch = Scratch::new("verify");
+    let input = scratch.write("text", &text());
+    let good = scratch.path("good.pp");
+    let bad = scratch.path("bad.pp");
+    let archive = scratch.path("text.ppa");
+
+    run_str(&["compress", p(&input), "-a", "bwt+mtf+zrle+rans", "--verify", "-o", p(&good)]).unwrap();
+    run_str(&["pack", p(&input), "-o", p(&archive)]).unwrap();
+    run_str(&["test", p(&good), p(&archive)]).unwrap();
+
+    let mut data = fs::read(&good).unwrap();
+    let middle = data.len() / 2;
+    data[middle] ^= 0x80;
+    fs::write(&bad, &data).unwrap();
+    // One bad file fails the whole run, after the others are checked
+    assert!(run_str(&["test", p(&good), p(&bad)]).is_err());
+}
+
+#[test]
+fn decompress_a_range() {
+    let scratch = Scratch::new("range");
+    let text = text();
+    let input = scratch.write("text", &text);
+    let compressed = scratch.path("text.pp");
+    let part = scratch.path("part");
+
+    run_str(&["compress", p(&input), "-a", "lz77+huffman", "--seekable", "-b", "4096", "-o", p(&compressed)]).unwrap();
+    for (range, expected) in [("12345:100", &text[12345..12445]), ("0:1", &text[..1]), ("70000", &text[70000..])] {
+        run_str(&["decompress", p(&compressed), "--range", range, "-o", p(&part)]).unwrap();
+        assert_eq!(fs::read(&part).unwrap(), expected, "{}", range);
+    }
+
+    let plain = scratch.path("plain.pp");
+    run_str(&["compress", p(&input), "-o", p(&plain)]).unwrap();
+    assert!(run_str(&["decompress", p(&plain), "--range", "0:10", "-o", p(&part)]).is_err());
+    assert!(run_str(&["decompress", p(&compressed), "--range", "ten", "-o", p(&part)]).is_err());
+}
+
+
+#[test]
+fn pack_and_unpack() {
+    let scratch = Scratch::new("pack");
+    let files = [("tree/a.txt", text()), ("tree/nested/b.bin", (0..=255).collect()), ("tree/nested/empty", Vec::new())];
+    for (name, data) in &files {
+        scratch.write(name, data);
+    }
+    let archive = scratch.path("tree.ppa");
+    let out = scratch.path("out");
+
+    run_str(&["pack", p(&scratch.path("tree")), "-o", p(&archive)]).unwrap();
+    run_str(&["unpack", p(&archive), "-C", p(&out)]).unwrap();
+    for (name, data) in &files {
+        assert_eq!(&fs::read(out.join(name)).unwrap(), data, "{}", name);
+    }
+
+    // Existing files are only replaced with --force
+    fs::write(out.join("tree/a.txt"), b"changed").unwrap();
+    assert!(run_str(&["unpack", p(&archive), "-C", p(&out)]).is_err());
+    assert_eq!(fs::read(out.join("tree/a.txt")).unwrap(), b"changed");
+    run_str(&["unpack", p(&archive), "-C", p(&out), "--force"]).unwrap();
+    assert_eq!(fs::read(out.join("tree/a.txt")).unwrap(), text());
+
+    let mut data = fs::read(&archive).unwrap();
+    data.truncate(data.len() - 1);
+    fs::write(&archive, &data).unwrap();
+    assert!(run_str(&["unpack", p(&archive), "-C", p(&scratch.path("again"))]).is_err());
+}
+
+
+#[test]
+fn batch_mode_compresses_directories() {
+    let scratch = Scratch::new("batch");
+    let files = [("dir/a.txt", text()), ("dir/sub/b.bin", (0..=255).collect()), ("dir/sub/empty", Vec::new())];
+    for (name, data) in &files {
+        scratch.write(name, data);
+    }
+    let dir = scratch.path("dir");
+    let a = scratch.path("dir/a.txt");
+
+    run_str(&["compress", "-R", p(&dir)]).unwrap();
+    assert!(!a.exists());
+    assert!(scratch.path("dir/sub/b.bin.pp").exists());
+
+    // Existing outputs are skipped without --force
+    scratch.write("dir/a.txt", b"in the way");
+    run_str(&["decompress", "--keep", p(&scratch.path("dir/a.txt.pp"))]).unwrap();
+    assert_eq!(fs::read(&a).unwrap(), b"in the way");
+    fs::remove_file(&a).unwrap();
+
+    run_str(&["decompress", "-R", p(&dir)]).unwrap();
+    for (name, data) in &files {
+        assert_eq!(&fs::read(scratch.path(name)).unwrap(), data, "{}", name);
+        assert!(!scratch.path(&format!("{}.pp", name)).exists());
+    }
+}
+
+
+#[test]
+fn bench_roundtrips_every_codec() {
+    let scratch = Scratch::new("bench");
+    scratch.write("corpus/text", &text()[..5000]);
+    scratch.write("corpus/bytes", &(0..=255).collect::<Vec<u8>>());
+    let corpus = scratch.path("corpus");
+
+    // Fails if any codec does not reproduce its input
+    run_str(&["bench", p(&corpus), "--format", "csv"]).unwrap();
+    run_str(&["bench", p(&corpus), "-a", "lz77", "-a", "bwt+mtf+zrle+huffman", "-n", "2", "--format", "json"]).unwrap();
+    assert!(run_str(&["bench", p(&scratch.path("missing"))]).is_err());
+}
+
+#[test]
+fn auto_records_the_chosen_spec() {
+    let scratch = Scratch::new("auto");
+    let input = scratch.write("text", &text());
+    let compressed = scratch.path("text.pp");
+    let restored = scratch.path("restored");
+
+    run_str(&["compress", p(&input), "-a", "auto", "-o", p(&compressed)]).unwrap();
+    let (header, _) = Header::parse(&fs::read(&compressed).unwrap()).unwrap().unwrap();
+    assert!(auto::DEFAULT_CANDIDATES.contains(&header.algorithm.as_str()), "{}", header.algorithm);
+    run_str(&["decompress", p(&compressed), "-o", p(&restored)]).unwrap();
+    assert_eq!(fs::read(&restored).unwrap(), text());
+}
+
+#[test]
+fn inspect_and_info() {
+    let scratch = Scratch::new("inspect");
+    let input = scratch.write("text", &text());
+    let compressed = scratch.path("text.pp");
+
+    let runs = [
+        vec!["-a", "huffman"],
+        vec!["-a", "rle+lz77+huffman"],
+        vec!["-a", "lzw"],
+        vec!["-a", "lz77", "-t", "2", "-b", "10000"],
+        vec!["-a", "lz77", "--seekable"],
+    ];
+    for args in &runs {
+        let mut compress = vec!["compress", p(&input), "-o", p(&compressed)];
+        compress.extend(args);
+        run_str(&compress).unwrap();
+        run_str(&["info", p(&compressed)]).unwrap();
+        run_str(&["inspect", p(&compressed)]).unwrap();
+        run_str(&["inspect", p(&compressed), "--json"]).unwrap();
+    }
+    assert!(run_str(&["inspect", p(&compressed), "--block", "1000"]).is_err());
+
+    let archive = scratch.path("text.ppa");
+    run_str(&["pack", p(&input), "-o", p(&archive)]).unwrap();
+    assert!(run_str(&["inspect", p(&archive)]).is_err());
+}
+
+#[test]
+fn compression_levels() {
+    let scratch = Scratch::new("levels");
+    let input = scratch.write("text", &text());
+    let fast = scratch.path("fast.pp");
+    let best = scratch.path("best.pp");
+    let restored = scratch.path("restored");
+
+    run_str(&["compress", p(&input), "-a", "lz77+huffman", "-1", "-o", p(&fast)]).unwrap();
+    run_str(&["compress", p(&input), "-a", "lz77+huffman", "--level", "9", "-o", p(&best)]).unwrap();
+    assert!(fs::metadata(&best).unwrap().len() <= fs::metadata(&fast).unwrap().len());
+    for compressed in [&fast, &best] {
+        run_str(&["decompress", p(compressed), "-o", p(&restored)]).unwrap();
+        assert_eq!(fs::read(&restored).unwrap(), text());
+    }
+    assert!(run_str(&["compress", p(&input), "--level", "10", "-o", p(&fast)]).is_err());
+}
+
+#[test]
+fn metadata_is_restored() {
+    let scratch = Scratch::new("metadata");
+    let files = [("dir/a.txt", text()), ("dir/b.bin", (0..=255).collect())];
+    for (name, data) in &files {
+        scratch.write(name, data);
+    }
+    let a = scratch.path("dir/a.txt");
+    let metadata = FileMetadata { mode: 0o640, mtime: 1_500_000_000 };
+    metadata.apply(&a).unwrap();
+
+    run_str(&["compress", "-R", p(&scratch.path("dir"))]).unwrap();
+    run_str(&["decompress", "-R", p(&scratch.path("dir"))]).unwrap();
+    assert_eq!(FileMetadata::from_path(&a).unwrap().mtime, metadata.mtime);
+    #[cfg(unix)]
+    assert_eq!(FileMetadata::from_path(&a).unwrap().mode & 0o777, 0o640);
+
+    // With -o the metadata is restored too, unless --no-preserve is given
+    let compressed = scratch.path("a.pp");
+    let restored = scratch.path("restored");
+    run_str(&["compress", p(&a), "-o", p(&compressed)]).unwrap();
+    run_str(&["decompress", p(&compressed), "-o", p(&restored)]).unwrap();
+    assert_eq!(FileMetadata::from_path(&restored).unwrap().mtime, metadata.mtime);
+    run_str(&["decompress", p(&compressed), "--no-preserve", "-o", p(&restored)]).unwrap();
+    assert_ne!(FileMetadata::from_path(&restored).unwrap().mtime, metadata.mtime);
+}
+
+#[test]
+fn batch_mode_uses_the_recorded_name() {
+    let scratch = Scratch::new("recorded");
+    let input = scratch.write("report.txt", &text());
+    let compressed = scratch.path("renamed.pp");
+    run_str(&["compress", p(&input), "-o", p(&compressed)]).unwrap();
+    fs::remove_file(&input).unwrap();
+
+    run_str(&["decompress", "--keep", p(&compressed)]).unwrap();
+    assert_eq!(fs::read(&input).unwrap(), text());
+    assert!(!scratch.path("renamed").exists());
+}
+
+
+#[test]
+fn analyze_reports() {
+    let scratch = Scratch::new("analyze");
+    for (name, data) in [("empty", Vec::new()), ("text", text())] {
+        let input = scratch.write(name, &data);
+        run_str(&["analyze", p(&input)]).unwrap();
+        run_str(&["analyze", p(&input), "-n", "0", "--json"]).unwrap();
+    }
+    assert!(run_str(&["analyze", p(&scratch.path("missing"))]).is_err());
+}
+
+#[test]
+fn usage_errors() {
+    assert!(run_str(&["compress", "-a", "nope", "-o", "x"]).is_err());
+    assert!(run_str(&["compress", "-a", "rle:2", "-o", "x"]).is_err());
+    assert!(run_str(&["frobnicate"]).is_err());
+    assert!(run_str(&["decompress", "a", "b", "-o", "x"]).is_err());
+}